@@ -1,7 +1,9 @@
 use std::ffi::OsStr;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use serde::{Deserialize, Serialize};
 
+use crate::source_port::SourcePort;
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Asset {
     pub path: PathBuf,
@@ -62,13 +64,13 @@ impl crate::Movable for Iwad {
 }
 
 impl crate::Argument for Mod {
-    fn get_prefix() -> &'static OsStr {
-        OsStr::new("-file")
+    fn get_prefix(port: &dyn SourcePort, path: &Path) -> &'static OsStr {
+        port.get_mod_prefix(path)
     }
 }
 
 impl crate::Argument for Iwad {
-    fn get_prefix() -> &'static OsStr {
-        OsStr::new("-iwad")
+    fn get_prefix(port: &dyn SourcePort, _path: &Path) -> &'static OsStr {
+        port.get_iwad_prefix()
     }
 }
\ No newline at end of file
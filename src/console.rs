@@ -0,0 +1,306 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::Child;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time;
+
+use crate::history::MapRecord;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ConsoleEvent {
+    // GZDoom prints `MAP01 - Entryway` under a separator bar whenever a level loads
+    MapStarted { map: String, title: String },
+    LevelCompleted,
+}
+
+pub fn parse_console_line(line: &str) -> Option<ConsoleEvent> {
+    let line = strip_console_formatting(line);
+    let line = line.trim();
+
+    // Completion messages differ between ports and mods, so match loosely
+    let lowercase = line.to_lowercase();
+    if lowercase.contains("level complete") || lowercase.contains("exited the level") {
+        return Some(ConsoleEvent::LevelCompleted);
+    }
+
+    let (map, title) = line.split_once(" - ")?;
+    let map = map.trim();
+    let title = title.trim();
+    if title.is_empty() || !is_map_name(map) {
+        return None;
+    }
+
+    Some(ConsoleEvent::MapStarted {
+        map: map.to_string(),
+        title: title.to_string(),
+    })
+}
+
+// Map lump names are at most 8 characters. Requiring a digit keeps out TITLEMAP and
+// most other lines that happen to contain a dash, like `MAP01`, `E1M1` or `HUB03`
+fn is_map_name(name: &str) -> bool {
+    name.len() <= 8
+        && name.starts_with(|c: char| c.is_ascii_uppercase())
+        && name.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+        && name.chars().any(|c| c.is_ascii_digit())
+}
+
+// Drops ANSI escapes, GZDoom's own `\x1c` color codes and the separator bar characters
+fn strip_console_formatting(line: &str) -> String {
+    let mut stripped = String::with_capacity(line.len());
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => {
+                if chars.next_if_eq(&'[').is_some() {
+                    // CSI sequences end with a byte in `@`..=`~`
+                    for c in chars.by_ref() {
+                        if ('@'..='~').contains(&c) {
+                            break;
+                        }
+                    }
+                }
+            }
+            // Followed by a single color letter, or a name like `\x1c[Gold]`
+            '\x1c' => {
+                if chars.next() == Some('[') {
+                    for c in chars.by_ref() {
+                        if c == ']' {
+                            break;
+                        }
+                    }
+                }
+            }
+            c if c.is_control() => {}
+            c => stripped.push(c),
+        }
+    }
+
+    stripped
+}
+
+// `MAP07` → (`MAP`, 7), `E2M3` → (`E2M`, 3)
+fn get_map_position(map: &str) -> Option<(&str, u32)> {
+    let prefix = map.trim_end_matches(|c: char| c.is_ascii_digit());
+    Some((prefix, map[prefix.len()..].parse().ok()?))
+}
+
+// Moving on to a later map of the same series, like `MAP07` → `MAP08` or `MAP15` → `MAP31`.
+// Going back to an earlier map is loading a save or starting a new game, not finishing one
+fn is_progression(from: &str, to: &str) -> bool {
+    match (get_map_position(from), get_map_position(to)) {
+        (Some((from_prefix, from_number)), Some((to_prefix, to_number))) => {
+            from_prefix == to_prefix && to_number > from_number
+        }
+        _ => false,
+    }
+}
+
+// Time spent per map, in the order they were first entered. A level counts as completed on
+// a completion message or when a later map of the same series starts right after it, so
+// warping ahead with `map` or `idclev`, or loading a save from a later map, counts too
+#[derive(Default)]
+pub struct MapTracker {
+    maps: Vec<MapRecord>,
+    current: Option<(usize, time::Instant)>,
+}
+
+impl MapTracker {
+    pub fn handle_line(&mut self, line: &str, at: time::Instant) {
+        match parse_console_line(line) {
+            Some(ConsoleEvent::MapStarted { map, title }) => {
+                if let Some((index, _)) = self.current
+                    && is_progression(&self.maps[index].map, &map)
+                {
+                    self.maps[index].completed = true;
+                }
+                self.close_current(at);
+
+                let index = match self.maps.iter().position(|record| record.map == map) {
+                    Some(index) => index,
+                    None => {
+                        self.maps.push(MapRecord {
+                            map,
+                            title,
+                            time: time::Duration::ZERO,
+                            completed: false,
+                        });
+                        self.maps.len() - 1
+                    }
+                };
+                self.current = Some((index, at));
+            }
+            Some(ConsoleEvent::LevelCompleted) => {
+                if let Some((index, _)) = self.current {
+                    self.maps[index].completed = true;
+                }
+            }
+            None => {}
+        }
+    }
+
+    pub fn finish(mut self, at: time::Instant) -> Vec<MapRecord> {
+        self.close_current(at);
+        self.maps
+    }
+
+    fn close_current(&mut self, at: time::Instant) {
+        if let Some((index, entered)) = self.current.take() {
+            self.maps[index].time += at.saturating_duration_since(entered);
+        }
+    }
+}
+
+struct CaptureState {
+    log: File,
+    tracker: MapTracker,
+}
+
+// Copies the game's stdout and stderr into its session log line by line, timestamping them on the
+// way so map changes can be timed. GZDoom flushes after every console print, so lines show up
+// as they happen even though stdout isn't a terminal
+pub struct OutputCapture {
+    state: Arc<Mutex<CaptureState>>,
+    readers: Vec<thread::JoinHandle<()>>,
+}
+
+impl OutputCapture {
+    // Expects `child` to be spawned with piped stdout and stderr
+    pub fn start(child: &mut Child, log: File) -> Self {
+        let state = Arc::new(Mutex::new(CaptureState {
+            log,
+            tracker: MapTracker::default(),
+        }));
+
+        let mut readers = Vec::new();
+        if let Some(stdout) = child.stdout.take() {
+            readers.push(Self::spawn_reader(stdout, state.clone()));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            readers.push(Self::spawn_reader(stderr, state.clone()));
+        }
+
+        Self { state, readers }
+    }
+
+    // Call once the game exited, waits for whatever output is still in the pipes
+    pub fn finish(self, at: time::Instant) -> Vec<MapRecord> {
+        for reader in self.readers {
+            let _ = reader.join();
+        }
+
+        let state = match Arc::try_unwrap(self.state) {
+            Ok(state) => state.into_inner().unwrap_or_else(|e| e.into_inner()),
+            // Only if a reader couldn't be joined, nothing sensible to return then
+            Err(_) => return vec![],
+        };
+        state.tracker.finish(at)
+    }
+
+    fn spawn_reader(source: impl Read + Send + 'static, state: Arc<Mutex<CaptureState>>) -> thread::JoinHandle<()> {
+        thread::spawn(move || {
+            let mut reader = BufReader::new(source);
+            let mut line = Vec::new();
+
+            while let Ok(read) = reader.read_until(b'\n', &mut line) {
+                if read == 0 {
+                    break;
+                }
+                let at = time::Instant::now();
+                let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+                // Losing a log line isn't worth stopping the capture over
+                let _ = state.log.write_all(&line);
+                state.tracker.handle_line(&String::from_utf8_lossy(&line), at);
+                line.clear();
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map_started(map: &str, title: &str) -> Option<ConsoleEvent> {
+        Some(ConsoleEvent::MapStarted {
+            map: map.to_string(),
+            title: title.to_string(),
+        })
+    }
+
+    #[test]
+    fn parses_map_start_lines() {
+        assert_eq!(parse_console_line("MAP01 - Entryway\n"), map_started("MAP01", "Entryway"));
+        assert_eq!(parse_console_line("E1M1 - Hangar"), map_started("E1M1", "Hangar"));
+        assert_eq!(parse_console_line("\x1b[1mMAP02 - Underhalls\x1b[0m"), map_started("MAP02", "Underhalls"));
+        assert_eq!(parse_console_line("\x1c+MAP03 - The Gantlet"), map_started("MAP03", "The Gantlet"));
+        assert_eq!(parse_console_line("\x1c[Gold]MAP04 - The Focus"), map_started("MAP04", "The Focus"));
+    }
+
+    #[test]
+    fn ignores_other_lines() {
+        assert_eq!(parse_console_line("TITLEMAP - Title"), None);
+        assert_eq!(parse_console_line("W_Init - Init WADfiles"), None);
+        assert_eq!(parse_console_line("MAP01 - "), None);
+        assert_eq!(parse_console_line("LONGMAPNAME1 - Too long"), None);
+        assert_eq!(parse_console_line("\x1d\x1e\x1e\x1f"), None);
+        assert_eq!(parse_console_line(""), None);
+    }
+
+    #[test]
+    fn parses_completion_lines() {
+        assert_eq!(parse_console_line("Level completed"), Some(ConsoleEvent::LevelCompleted));
+        assert_eq!(parse_console_line("Player exited the level."), Some(ConsoleEvent::LevelCompleted));
+    }
+
+    fn track(lines: &[(&str, u64)], end: u64) -> Vec<MapRecord> {
+        let start = time::Instant::now();
+        let at = |secs| start + time::Duration::from_secs(secs);
+        let mut tracker = MapTracker::default();
+        for (line, secs) in lines {
+            tracker.handle_line(line, at(*secs));
+        }
+        tracker.finish(at(end))
+    }
+
+    #[test]
+    fn times_maps_and_completes_on_progression() {
+        let maps = track(&[("MAP01 - Entryway", 0), ("MAP02 - Underhalls", 60)], 100);
+        assert_eq!(maps.len(), 2);
+        assert_eq!((maps[0].map.as_str(), maps[0].time.as_secs(), maps[0].completed), ("MAP01", 60, true));
+        assert_eq!((maps[1].map.as_str(), maps[1].time.as_secs(), maps[1].completed), ("MAP02", 40, false));
+    }
+
+    #[test]
+    fn going_back_is_not_completion() {
+        // Started over from the menu on MAP05, which leaves it unfinished
+        let maps = track(
+            &[("MAP01 - Entryway", 0), ("MAP05 - The Waste Tunnels", 10), ("MAP01 - Entryway", 20)],
+            30,
+        );
+        assert_eq!(maps.len(), 2);
+        assert_eq!((maps[0].time.as_secs(), maps[0].completed), (20, true));
+        assert_eq!((maps[1].time.as_secs(), maps[1].completed), (10, false));
+    }
+
+    #[test]
+    fn other_series_is_not_completion() {
+        let maps = track(&[("E1M8 - Phobos Anomaly", 0), ("E2M1 - Deimos Anomaly", 10)], 20);
+        assert!(!maps[0].completed);
+    }
+
+    #[test]
+    fn completion_message_completes_current_map() {
+        let maps = track(&[("MAP30 - Icon of Sin", 0), ("Level completed", 50)], 60);
+        assert_eq!((maps[0].time.as_secs(), maps[0].completed), (60, true));
+    }
+
+    #[test]
+    fn restarting_a_map_adds_up() {
+        let maps = track(&[("MAP01 - Entryway", 0), ("MAP01 - Entryway", 30)], 45);
+        assert_eq!(maps.len(), 1);
+        assert_eq!((maps[0].time.as_secs(), maps[0].completed), (45, false));
+    }
+}
@@ -0,0 +1,79 @@
+use std::ffi::OsString;
+use std::fmt;
+
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+
+// Untagged, so `instance.toml` keeps them as plain TOML values, e.g. `gl_texture_filter = 4`
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(untagged)]
+pub enum CvarValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+}
+
+impl fmt::Display for CvarValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CvarValue::Bool(value) => write!(f, "{}", value),
+            CvarValue::Int(value) => write!(f, "{}", value),
+            CvarValue::Float(value) => write!(f, "{}", value),
+            CvarValue::String(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+impl CvarValue {
+    pub fn to_os_string(&self) -> OsString {
+        OsString::from(self.to_string())
+    }
+}
+
+pub const MAX_CVAR_NAME_LENGTH: usize = 64;
+
+pub fn validate_cvar(name: &str, value: &CvarValue) -> Result<()> {
+    let mut chars = name.chars();
+    let valid_name = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && name.len() <= MAX_CVAR_NAME_LENGTH;
+
+    if !valid_name {
+        bail!("Invalid cvar name `{}`", name);
+    }
+
+    match value {
+        CvarValue::Float(value) if !value.is_finite() => {
+            bail!("Value of {} must be a finite number", name);
+        }
+        // The engine turns `+` arguments back into console commands, where these would
+        // end the command or start a new one
+        CvarValue::String(value) if value.contains(['"', ';', '\n', '\r', '\0']) => {
+            bail!("Value of {} contains characters that can't be passed on the command line", name);
+        }
+        // The engine stops collecting a `+` command's words at the next one starting with
+        // either, and unlike numbers a string can't get a leading space instead
+        CvarValue::String(value) if value.starts_with(['-', '+']) => {
+            bail!("Value of {} can't start with `-` or `+` on the command line", name);
+        }
+        _ => Ok(()),
+    }
+}
+
+// `+set <name> <value>`, understood by every ZDoom descendant and Odamex.
+// A word starting with `-` ends the `+set` command, so negative numbers go with a leading space.
+// The engine quotes words containing spaces when turning them back into a console command,
+// and number parsing skips the space again. Bools go as `1`/`0`, not every engine parses `true`
+pub fn get_set_cvar_arguments(name: &str, value: &CvarValue) -> Vec<OsString> {
+    let value = match value {
+        CvarValue::Bool(value) => OsString::from(if *value { "1" } else { "0" }),
+        CvarValue::Int(_) | CvarValue::Float(_) if value.to_string().starts_with('-') => {
+            OsString::from(format!(" {}", value))
+        }
+        _ => value.to_os_string(),
+    };
+    vec![OsString::from("+set"), OsString::from(name), value]
+}
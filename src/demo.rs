@@ -0,0 +1,109 @@
+use std::fs;
+use std::io::Error;
+use std::path::{Path, PathBuf};
+use std::time;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::launch_options::LaunchOptions;
+use crate::traits::Saveable;
+use crate::{SaveableDefaultPath, SulphurConfig};
+
+pub struct Demodir;
+
+impl Demodir {
+    pub fn get_dir_name() -> &'static str {
+        "demos"
+    }
+    pub fn get() -> Result<PathBuf, Error> {
+        SulphurConfig::get_dir()
+            .place_data_file(Demodir::get_dir_name())
+            .map_err(Error::other)
+    }
+}
+
+// Written next to each recorded demo, since a demo only plays back with the same assets loaded
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DemoInfo {
+    pub recorded: time::SystemTime,
+    pub engine: Option<String>,
+    pub iwads: Vec<PathBuf>,
+    pub mods: Vec<PathBuf>,
+}
+
+impl Saveable for DemoInfo {}
+
+#[derive(Clone)]
+pub struct Demo {
+    pub path: PathBuf,
+    pub size: u64,
+    pub modified: Option<time::SystemTime>,
+    pub info: Option<DemoInfo>,
+}
+
+impl Demo {
+    pub const EXTENSION: &'static str = "lmp";
+    pub const INFO_EXTENSION: &'static str = "toml";
+
+    pub fn get_info_path(demo_path: &Path) -> PathBuf {
+        demo_path.with_extension(Self::INFO_EXTENSION)
+    }
+
+    pub fn from_path(path: PathBuf) -> Self {
+        let metadata = fs::metadata(&path).ok();
+        let info_path = Self::get_info_path(&path);
+        Self {
+            size: metadata.as_ref().map(|m| m.len()).unwrap_or(0),
+            modified: metadata.and_then(|m| m.modified().ok()),
+            info: info_path
+                .is_file()
+                .then(|| DemoInfo::load_from(info_path).ok())
+                .flatten(),
+            path,
+        }
+    }
+
+    // Recording date if known, file modification time otherwise
+    pub fn get_date(&self) -> Option<time::SystemTime> {
+        self.info.as_ref().map(|info| info.recorded).or(self.modified)
+    }
+
+    // Newest first
+    pub fn list(dir: &Path) -> Result<Vec<Demo>> {
+        if !dir.is_dir() {
+            return Ok(vec![]);
+        }
+
+        let mut demos: Vec<Demo> = fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.is_file()
+                    && path
+                        .extension()
+                        .is_some_and(|ext| ext.eq_ignore_ascii_case(Self::EXTENSION))
+            })
+            .map(Demo::from_path)
+            .collect();
+
+        demos.sort_by_key(|demo| std::cmp::Reverse(demo.get_date()));
+        Ok(demos)
+    }
+
+    pub fn get_playback_options(&self) -> LaunchOptions {
+        LaunchOptions {
+            playdemo: Some(self.path.clone()),
+            ..Default::default()
+        }
+    }
+
+    pub fn delete(self) -> Result<()> {
+        fs::remove_file(&self.path)?;
+        let info_path = Self::get_info_path(&self.path);
+        if info_path.is_file() {
+            fs::remove_file(info_path)?;
+        }
+        Ok(())
+    }
+}
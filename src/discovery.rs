@@ -0,0 +1,100 @@
+use std::collections::HashSet;
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use xdg::BaseDirectories;
+
+use crate::engine::Engine;
+use crate::source_port::SourcePortKind;
+
+const EXTRA_BIN_DIRS: [&str; 3] = ["/usr/games", "/usr/local/games", "/opt/bin"];
+const SYSTEM_FLATPAK_EXPORTS: &str = "/var/lib/flatpak/exports/bin";
+
+// Looks through $PATH, common install locations and Flatpak exports for known engines.
+// Nothing is executed, so the returned engines never carry a version label
+pub fn discover_engines() -> Vec<Engine> {
+    let mut engines = Vec::new();
+    let mut seen = HashSet::new();
+
+    for dir in get_search_dirs() {
+        for kind in SourcePortKind::ALL {
+            for name in kind.get_port().get_executable_names() {
+                let path = dir.join(name);
+                if !is_executable(&path) {
+                    continue;
+                }
+                // Symlinks in /usr/bin and /usr/games often point at the same binary
+                let canonical = fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+                if !seen.insert(canonical) {
+                    continue;
+                }
+                let label = if engines.iter().any(|e: &Engine| e.name == *name) {
+                    format!("{} ({})", name, dir.display())
+                } else {
+                    name.to_string()
+                };
+                engines.push(Engine::new(label, quote_path(&path), kind));
+            }
+        }
+    }
+
+    for dir in get_flatpak_export_dirs() {
+        for kind in SourcePortKind::ALL {
+            for id in kind.get_port().get_flatpak_ids() {
+                // User and system installations of the same app launch the same way
+                if dir.join(id).exists() && seen.insert(PathBuf::from(id)) {
+                    let name = format!("{} (Flatpak)", kind.get_port().get_name());
+                    let command = OsString::from(format!("flatpak run {}", id));
+                    let mut engine = Engine::new(name, command, kind);
+                    engine.flatpak = Some(id.to_string());
+                    engines.push(engine);
+                }
+            }
+        }
+    }
+
+    engines
+}
+
+fn get_search_dirs() -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = env::var_os("PATH")
+        .map(|path| env::split_paths(&path).collect())
+        .unwrap_or_default();
+
+    dirs.extend(EXTRA_BIN_DIRS.iter().map(PathBuf::from));
+    if let Some(home) = env::var_os("HOME") {
+        dirs.push(Path::new(&home).join(".local/bin"));
+    }
+
+    let mut seen = HashSet::new();
+    dirs.retain(|dir| dir.is_absolute() && seen.insert(dir.clone()));
+    dirs
+}
+
+fn get_flatpak_export_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if let Ok(base) = BaseDirectories::new() {
+        dirs.push(base.get_data_home().join("flatpak/exports/bin"));
+    }
+    dirs.push(PathBuf::from(SYSTEM_FLATPAK_EXPORTS));
+    dirs
+}
+
+fn is_executable(path: &Path) -> bool {
+    fs::metadata(path)
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+// Engine commands get split like a shell would, so paths with spaces need quoting
+fn quote_path(path: &Path) -> OsString {
+    let bytes = path.as_os_str().as_bytes();
+    match shlex::bytes::try_quote(bytes) {
+        Ok(quoted) => OsString::from_vec(quoted.into_owned()),
+        Err(_) => OsStr::from_bytes(bytes).to_os_string(),
+    }
+}
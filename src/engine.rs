@@ -0,0 +1,59 @@
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::engine_config::ConfigRoots;
+use crate::flatpak;
+use crate::source_port::{SourcePort, SourcePortKind};
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Engine {
+    pub name: String,
+    pub command: OsString,
+    #[serde(default)]
+    pub port: SourcePortKind,
+    #[serde(default)]
+    pub version: Option<String>,
+    // Set for Flatpak installs, commands starting with `flatpak run` are detected without it
+    #[serde(default)]
+    pub flatpak: Option<String>,
+    // Passed to every instance running on this engine
+    #[serde(default)]
+    pub additional_params: Vec<OsString>,
+}
+
+impl Engine {
+    pub fn new(name: String, command: OsString, port: SourcePortKind) -> Self {
+        Self {
+            name,
+            flatpak: flatpak::parse_app_id(&command),
+            command,
+            port,
+            version: None,
+            additional_params: Vec::new(),
+        }
+    }
+
+    pub fn get_flatpak_app(&self) -> Option<String> {
+        self.flatpak
+            .clone()
+            .or_else(|| flatpak::parse_app_id(&self.command))
+    }
+
+    pub fn get_port(&self) -> &'static dyn SourcePort {
+        self.port.get_port()
+    }
+
+    pub fn get_global_config_path(&self) -> Option<PathBuf> {
+        let roots = ConfigRoots::for_engine(self)?;
+        self.get_port().get_global_config_path(&roots)
+    }
+
+    pub fn get_display_name(&self) -> String {
+        match &self.version {
+            Some(version) => format!("{} ({})", self.name, version),
+            None => self.name.clone(),
+        }
+    }
+}
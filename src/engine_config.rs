@@ -0,0 +1,73 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+use xdg::BaseDirectories;
+
+use crate::engine::Engine;
+
+// Base directories an engine resolves its own config location against.
+// Flatpak apps get their XDG dirs remapped under ~/.var/app/<id>
+pub struct ConfigRoots {
+    pub home: PathBuf,
+    pub config_home: PathBuf,
+    pub data_home: PathBuf,
+}
+
+impl ConfigRoots {
+    pub fn for_engine(engine: &Engine) -> Option<Self> {
+        let home = PathBuf::from(env::var_os("HOME")?);
+
+        if let Some(app_id) = engine.get_flatpak_app() {
+            let app_dir = home.join(".var/app").join(app_id);
+            return Some(Self {
+                config_home: app_dir.join("config"),
+                data_home: app_dir.join("data"),
+                home,
+            });
+        }
+
+        let base = BaseDirectories::new().ok()?;
+        Some(Self {
+            config_home: base.get_config_home(),
+            data_home: base.get_data_home(),
+            home,
+        })
+    }
+}
+
+pub enum ConfigSeed {
+    Empty,
+    // Any config file to start from, e.g. one kept around for a certain kind of modpack
+    Template(PathBuf),
+    // Copy of the engine's own global config, falls back to an empty file if there's none yet
+    Global,
+}
+
+impl ConfigSeed {
+    pub fn write_to(&self, dest: &Path, engine: &Engine) -> Result<()> {
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let source = match self {
+            ConfigSeed::Empty => None,
+            ConfigSeed::Template(path) => {
+                if !path.is_file() {
+                    bail!("Config template {} not found", path.display());
+                }
+                Some(path.clone())
+            }
+            ConfigSeed::Global => engine.get_global_config_path().filter(|path| path.is_file()),
+        };
+
+        match source {
+            Some(source) => fs::copy(&source, dest)
+                .map(|_| ())
+                .with_context(|| format!("Failed to copy {} to {}", source.display(), dest.display())),
+            None => fs::write(dest, "")
+                .with_context(|| format!("Failed to write to {}", dest.display())),
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context, Result, bail};
+
+use crate::launch::split_command;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct FilesystemAccess {
+    pub path: PathBuf,
+    pub read_only: bool,
+}
+
+impl FilesystemAccess {
+    pub fn read_only(path: PathBuf) -> Self {
+        Self { path, read_only: true }
+    }
+
+    pub fn read_write(path: PathBuf) -> Self {
+        Self { path, read_only: false }
+    }
+
+    pub fn to_flatpak_arg(&self) -> OsString {
+        let mut arg = OsString::from("--filesystem=");
+        arg.push(self.path.as_os_str());
+        if self.read_only {
+            arg.push(":ro");
+        }
+        arg
+    }
+}
+
+#[derive(Clone)]
+pub struct FlatpakSandbox {
+    pub app_id: String,
+    pub filesystems: Vec<FilesystemAccess>,
+}
+
+impl FlatpakSandbox {
+    // Drops entries already covered by another one, a writable parent also covers read-only children
+    pub fn new(app_id: String, mut requested: Vec<FilesystemAccess>) -> Self {
+        requested.sort_by(|a, b| a.path.cmp(&b.path).then(a.read_only.cmp(&b.read_only)));
+
+        let mut filesystems: Vec<FilesystemAccess> = Vec::new();
+        for access in requested {
+            let covered = filesystems.iter().any(|existing| {
+                access.path.starts_with(&existing.path) && (access.read_only || !existing.read_only)
+            });
+            if !covered {
+                filesystems.push(access);
+            }
+        }
+
+        Self { app_id, filesystems }
+    }
+
+    pub fn get_arguments(&self) -> impl Iterator<Item = OsString> + '_ {
+        self.filesystems.iter().map(|access| access.to_flatpak_arg())
+    }
+
+    // Flatpak only exposes directories that exist at launch time. Writable entries are always
+    // directories, files only ever get read-only access
+    pub fn create_writable_dirs(&self) -> Result<()> {
+        for access in self.filesystems.iter().filter(|access| !access.read_only) {
+            fs::create_dir_all(&access.path)
+                .with_context(|| format!("Failed to create {}", access.path.display()))?;
+        }
+        Ok(())
+    }
+}
+
+// `flatpak run` options that take a value, which may come as the next word instead of after a `=`
+const VALUE_OPTIONS: [&str; 35] = [
+    "--arch",
+    "--branch",
+    "--command",
+    "--cwd",
+    "--runtime",
+    "--runtime-version",
+    "--commit",
+    "--runtime-commit",
+    "--installation",
+    "--app-path",
+    "--usr-path",
+    "--parent-pid",
+    "--instance-id-fd",
+    "--env-fd",
+    "--env",
+    "--unset-env",
+    "--filesystem",
+    "--nofilesystem",
+    "--share",
+    "--unshare",
+    "--socket",
+    "--nosocket",
+    "--device",
+    "--nodevice",
+    "--allow",
+    "--disallow",
+    "--persist",
+    "--own-name",
+    "--talk-name",
+    "--no-talk-name",
+    "--system-own-name",
+    "--system-talk-name",
+    "--system-no-talk-name",
+    "--add-policy",
+    "--remove-policy",
+];
+
+// Picks the app id out of commands like `flatpak run --branch stable org.zdoom.GZDoom`
+pub fn parse_app_id(command: &OsStr) -> Option<String> {
+    let words = split_command(command);
+    let mut words = words.iter().map(|word| word.to_string_lossy());
+
+    let program = words.next()?;
+    if Path::new(program.as_ref()).file_name()? != "flatpak" || words.next()? != "run" {
+        return None;
+    }
+    while let Some(word) = words.next() {
+        if !word.starts_with('-') {
+            return Some(word.into_owned());
+        }
+        if VALUE_OPTIONS.contains(&word.as_ref()) {
+            words.next();
+        }
+    }
+    None
+}
+
+struct FlatpakProcess {
+    instance: String,
+    pid: u32,
+    child_pid: u32,
+}
+
+fn list_processes(app_id: &str) -> Result<Vec<FlatpakProcess>> {
+    let output = Command::new("flatpak")
+        .args(["ps", "--columns=instance,pid,child-pid,application"])
+        .output()
+        .context("Failed to run flatpak ps")?;
+
+    if !output.status.success() {
+        bail!("flatpak ps exited with {}", output.status);
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let columns: Vec<&str> = line.split_whitespace().collect();
+            match columns.as_slice() {
+                [instance, pid, child_pid, app, ..] if *app == app_id => Some(FlatpakProcess {
+                    instance: instance.to_string(),
+                    pid: pid.parse().ok()?,
+                    child_pid: child_pid.parse().ok()?,
+                }),
+                _ => None,
+            }
+        })
+        .collect())
+}
+
+fn get_parent_pid(pid: u32) -> Option<u32> {
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // The command name may contain spaces, so fields are counted from the closing paren
+    let after_name = &stat[stat.rfind(')')? + 1..];
+    after_name.split_whitespace().nth(1)?.parse().ok()
+}
+
+// In the foreground `flatpak run` execs into bwrap, so the sandbox's bwrap process is our wrapper
+// itself. Some setups fork it instead, then it's a child of the wrapper
+fn find_process(app_id: &str, wrapper_pid: u32) -> Result<Option<FlatpakProcess>> {
+    let mut processes = list_processes(app_id)?;
+    let position = processes
+        .iter()
+        .position(|process| process.pid == wrapper_pid || get_parent_pid(process.pid) == Some(wrapper_pid));
+    Ok(position.map(|i| processes.swap_remove(i)))
+}
+
+pub fn get_game_pid(app_id: &str, wrapper_pid: u32) -> Result<Option<u32>> {
+    Ok(find_process(app_id, wrapper_pid)?.map(|process| process.child_pid))
+}
+
+pub fn kill_game(app_id: &str, wrapper_pid: u32) -> Result<()> {
+    let target = match find_process(app_id, wrapper_pid)? {
+        Some(process) => process.instance,
+        None => bail!("No running sandbox of {} belongs to this session", app_id),
+    };
+
+    let status = Command::new("flatpak")
+        .args(["kill", &target])
+        .status()
+        .context("Failed to run flatpak kill")?;
+
+    if !status.success() {
+        bail!("flatpak kill exited with {}", status);
+    }
+    Ok(())
+}
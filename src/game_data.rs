@@ -1,11 +1,15 @@
-use std::ffi::OsStr;
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 use serde::{Deserialize, Serialize};
 use std::ffi::OsString;
 
-use crate::{SaveableDefaultPath, SulphurConfig};
+use anyhow::{Result, bail};
+
 use crate::asset::{Iwad, Mod};
-use crate::utils::get_arguments;
+use crate::cvars::{CvarValue, validate_cvar};
+use crate::launch_options::LaunchOptions;
+use crate::source_port::SourcePort;
+use crate::utils::{get_absolute_data_path, get_arguments, get_enabled};
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct GameData {
@@ -13,52 +17,127 @@ pub struct GameData {
     pub mods: Vec<Mod>,
     pub savedir: PathBuf,
     pub additional_params: Vec<OsString>,
+    #[serde(default)]
+    pub environment: BTreeMap<String, String>,
+    // Relative paths are resolved against the sulphur data dir, like `savedir`
+    #[serde(default)]
+    pub working_dir: Option<PathBuf>,
+    #[serde(default)]
+    pub launch_options: LaunchOptions,
+    // Instance-owned engine config passed through `-config`, relative to the sulphur data dir
+    #[serde(default)]
+    pub config_file: Option<PathBuf>,
+    #[serde(default)]
+    pub cvars: BTreeMap<String, CvarValue>,
 }
 
 impl GameData {
-    pub fn get_iwad_parameters(&self) -> OsString {
-        get_arguments(&self.iwads)
-            .collect::<Vec<_>>()
-            .join(OsStr::new(" "))
-            .into()
+    pub fn get_iwad_parameters(&self, port: &dyn SourcePort) -> Vec<OsString> {
+        get_arguments(&self.iwads, port)
     }
 
-    pub fn get_mods_parameters(&self) -> OsString {
-        get_arguments(&self.mods)
-            .collect::<Vec<_>>()
-            .join(OsStr::new(" "))
-            .into()
+    pub fn get_mods_parameters(&self, port: &dyn SourcePort) -> Vec<OsString> {
+        get_arguments(&self.mods, port)
     }
 
-    pub fn get_parameters(&self) -> OsString {
-        vec![
-            self.get_iwad_parameters(),
-            self.get_mods_parameters(),
-            [OsStr::new("-savedir"), &self.get_absolute_savedir().unwrap().into_os_string()].join(OsStr::new(" ")),
-            self.additional_params.join(OsStr::new(" ")),
-        ]
-        .join(OsStr::new(" "))
+    pub fn get_savedir_parameters(&self, port: &dyn SourcePort) -> Vec<OsString> {
+        match (port.get_savedir_prefix(), self.get_absolute_savedir()) {
+            (Some(prefix), Some(savedir)) => vec![prefix.to_os_string(), savedir.into_os_string()],
+            _ => Vec::new(),
+        }
     }
 
-    pub fn get_savedir(&self) -> &Path {
-        &self.savedir
+    pub fn get_config_parameters(&self, port: &dyn SourcePort) -> Vec<OsString> {
+        match (port.get_config_prefix(), self.get_absolute_config_file()) {
+            (Some(prefix), Some(config)) => vec![prefix.to_os_string(), config.into_os_string()],
+            _ => Vec::new(),
+        }
     }
 
-    pub fn get_absolute_savedir(&self) -> Option<PathBuf> {
-        let path= self.get_savedir();
-
-        if path.is_absolute() {
-            return Some(path.to_path_buf());
+    pub fn get_cvar_parameters(&self, port: &dyn SourcePort) -> Result<Vec<OsString>> {
+        let mut parameters = Vec::new();
+        for (name, value) in &self.cvars {
+            validate_cvar(name, value)?;
+            match port.get_cvar_arguments(name, value) {
+                Some(arguments) => parameters.extend(arguments),
+                None => bail!("{} can't set cvars from the command line", port.get_name()),
+            }
         }
+        Ok(parameters)
+    }
 
-        if let Ok(base_dir) = SulphurConfig::get_dir().place_data_file("") {
-            return Some(base_dir.join(path));
+    // Everything generated from the instance's typed settings, `additional_params` is layered
+    // separately. Every element is passed to the engine as its own argument, nothing gets split
+    // or quoted. `options` should already have any per-launch overrides applied
+    pub fn get_parameters(&self, port: &dyn SourcePort, options: &LaunchOptions) -> Result<Vec<OsString>> {
+        self.check_loadable(port)?;
+        let mut parameters = self.get_iwad_parameters(port);
+        parameters.extend(self.get_mods_parameters(port));
+        parameters.extend(self.get_savedir_parameters(port));
+        parameters.extend(self.get_config_parameters(port));
+        parameters.extend(self.get_cvar_parameters(port)?);
+        parameters.extend(options.get_arguments(port)?);
+        Ok(parameters)
+    }
+
+    // Vanilla style engines would just fail to start on a .pk3
+    pub fn check_loadable(&self, port: &dyn SourcePort) -> Result<()> {
+        let unloadable: Vec<String> = get_enabled(&self.iwads)
+            .map(|iwad| &iwad.as_ref().path)
+            .chain(get_enabled(&self.mods).map(|mod_asset| &mod_asset.as_ref().path))
+            .filter(|path| !port.can_load(path))
+            .map(|path| path.display().to_string())
+            .collect();
+
+        if !unloadable.is_empty() {
+            bail!("{} can't load archives: {}", port.get_name(), unloadable.join(", "));
         }
+        Ok(())
+    }
+
+    pub fn get_savedir(&self) -> &Path {
+        &self.savedir
+    }
 
-        None
+    pub fn get_absolute_savedir(&self) -> Option<PathBuf> {
+        get_absolute_data_path(self.get_savedir())
     }
 
     pub fn set_savedir(&mut self, new: PathBuf) {
         self.savedir = new;
     }
+
+    pub fn get_absolute_working_dir(&self) -> Option<PathBuf> {
+        self.working_dir.as_deref().and_then(get_absolute_data_path)
+    }
+
+    pub fn set_working_dir(&mut self, new: Option<PathBuf>) {
+        self.working_dir = new;
+    }
+
+    pub fn get_absolute_config_file(&self) -> Option<PathBuf> {
+        self.config_file.as_deref().and_then(get_absolute_data_path)
+    }
+
+    pub fn set_cvar(&mut self, name: String, value: CvarValue) -> Result<()> {
+        validate_cvar(&name, &value)?;
+        self.cvars.insert(name, value);
+        Ok(())
+    }
+
+    pub fn remove_cvar(&mut self, name: &str) -> Option<CvarValue> {
+        self.cvars.remove(name)
+    }
+
+    pub fn set_env(&mut self, key: String, value: String) -> Result<()> {
+        if key.is_empty() || key.contains(['=', '\0']) || value.contains('\0') {
+            bail!("Invalid environment variable {}", key);
+        }
+        self.environment.insert(key, value);
+        Ok(())
+    }
+
+    pub fn remove_env(&mut self, key: &str) -> Option<String> {
+        self.environment.remove(key)
+    }
 }
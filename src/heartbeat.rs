@@ -0,0 +1,123 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::history::SessionRecord;
+use crate::session::SessionOutcome;
+use crate::traits::Saveable;
+
+// Written next to the session history while a game runs and removed once the session is recorded.
+// One that's still around on the next start means the launcher never saw the game exit
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SessionMarker {
+    pub pid: u32,
+    pub start: time::SystemTime,
+    pub last_heartbeat: time::SystemTime,
+    // Measured with a monotonic clock, so changing the system time mid-session doesn't skew it
+    pub elapsed: time::Duration,
+    pub engine: Option<String>,
+    pub iwads: Vec<PathBuf>,
+    pub mods: Vec<PathBuf>,
+}
+
+impl Saveable for SessionMarker {}
+
+impl SessionMarker {
+    pub const FILENAME: &'static str = "running.toml";
+    pub const HEARTBEAT_INTERVAL: time::Duration = time::Duration::from_secs(30);
+
+    // A power loss mid-write shouldn't leave a marker that can't be read
+    pub fn write_to(&self, path: &Path) -> Result<()> {
+        self.save_atomically(path)
+    }
+
+    pub fn load_if_exists(path: &Path) -> Result<Option<Self>> {
+        if !path.is_file() {
+            return Ok(None);
+        }
+        Self::load_from(path.to_path_buf()).map(Some)
+    }
+
+    // The game might have outlived the launcher, in which case the session isn't over yet.
+    // A reused PID just delays recovery until that process is gone
+    pub fn is_game_running(&self) -> bool {
+        Path::new("/proc").join(self.pid.to_string()).exists()
+    }
+
+    // The game ran at least until the last heartbeat, anything after that is lost
+    pub fn into_record(self) -> SessionRecord {
+        SessionRecord {
+            start: self.start,
+            end: self.last_heartbeat,
+            duration: self.elapsed,
+            engine: self.engine,
+            iwads: self.iwads,
+            mods: self.mods,
+            outcome: SessionOutcome::Interrupted,
+            maps: vec![],
+        }
+    }
+}
+
+// Keeps a `SessionMarker` up to date from a background thread. Dropping it stops the thread but
+// leaves the marker in place, only `finish` removes it
+pub struct Heartbeat {
+    path: PathBuf,
+    stop: Option<mpsc::Sender<()>>,
+    handle: Option<thread::JoinHandle<()>>,
+}
+
+impl Heartbeat {
+    pub fn start(path: PathBuf, mut marker: SessionMarker, started: time::Instant) -> Result<Self> {
+        marker.write_to(&path)?;
+
+        let (stop, stopped) = mpsc::channel();
+        let thread_path = path.clone();
+        let handle = thread::Builder::new()
+            .name("sulphur-heartbeat".to_string())
+            .spawn(move || {
+                while let Err(mpsc::RecvTimeoutError::Timeout) = stopped.recv_timeout(SessionMarker::HEARTBEAT_INTERVAL) {
+                    marker.last_heartbeat = time::SystemTime::now();
+                    marker.elapsed = started.elapsed();
+                    // Nowhere to report this, a missed heartbeat only makes recovery less precise
+                    let _ = marker.write_to(&thread_path);
+                }
+            })
+            .context("Failed to start heartbeat thread")?;
+
+        Ok(Self {
+            path,
+            stop: Some(stop),
+            handle: Some(handle),
+        })
+    }
+
+    pub fn finish(mut self) -> Result<()> {
+        self.stop_thread();
+        match fs::remove_file(&self.path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(e).with_context(|| format!("Failed to remove {}", self.path.display()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn stop_thread(&mut self) {
+        // Dropping the sender wakes the thread up right away
+        self.stop.take();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for Heartbeat {
+    fn drop(&mut self) {
+        self.stop_thread();
+    }
+}
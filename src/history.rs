@@ -0,0 +1,108 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::session::SessionOutcome;
+use crate::traits::Saveable;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SessionRecord {
+    pub start: time::SystemTime,
+    pub end: time::SystemTime,
+    pub duration: time::Duration,
+    pub engine: Option<String>,
+    pub iwads: Vec<PathBuf>,
+    pub mods: Vec<PathBuf>,
+    pub outcome: SessionOutcome,
+    // Parsed from the engine's console output, empty for ports that don't print map changes
+    #[serde(default)]
+    pub maps: Vec<MapRecord>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct MapRecord {
+    // Lump name, e.g. `MAP01`
+    pub map: String,
+    pub title: String,
+    pub time: time::Duration,
+    pub completed: bool,
+}
+
+// A map's totals over every recorded session
+#[derive(Clone)]
+pub struct MapStats {
+    pub map: String,
+    pub title: String,
+    pub time: time::Duration,
+    pub completed: bool,
+    pub sessions: usize,
+}
+
+// Lives in the instance's data dir instead of `config.toml`, since it only ever grows
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct SessionHistory {
+    #[serde(default)]
+    pub sessions: Vec<SessionRecord>,
+}
+
+impl Saveable for SessionHistory {}
+
+impl SessionHistory {
+    pub const FILENAME: &'static str = "sessions.toml";
+
+    // A missing file just means nothing was played yet
+    pub fn load_or_default(path: &Path) -> Result<Self> {
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+        Self::load_from(path.to_path_buf())
+    }
+
+    pub fn append_to(path: &Path, record: SessionRecord) -> Result<()> {
+        let mut history = Self::load_or_default(path)?;
+        history.sessions.push(record);
+        // A truncated history would make every later append fail
+        history.save_atomically(path)
+    }
+
+    // Sessions that started inside `[from, to)`
+    pub fn get_sessions_between(&self, from: time::SystemTime, to: time::SystemTime) -> Vec<&SessionRecord> {
+        self.sessions
+            .iter()
+            .filter(|session| session.start >= from && session.start < to)
+            .collect()
+    }
+
+    pub fn get_longest_session(&self) -> Option<&SessionRecord> {
+        self.sessions.iter().max_by_key(|session| session.duration)
+    }
+
+    pub fn get_last_session(&self) -> Option<&SessionRecord> {
+        self.sessions.iter().max_by_key(|session| session.start)
+    }
+
+    pub fn get_total_playtime(&self) -> time::Duration {
+        self.sessions.iter().map(|session| session.duration).sum()
+    }
+
+    // Sorted by lump name, which puts `MAP01`..`MAP32` and `E1M1`..`E4M9` in order
+    pub fn get_map_stats(&self) -> Vec<MapStats> {
+        let mut maps: BTreeMap<&str, MapStats> = BTreeMap::new();
+        for record in self.sessions.iter().flat_map(|session| &session.maps) {
+            let stats = maps.entry(&record.map).or_insert_with(|| MapStats {
+                map: record.map.clone(),
+                title: record.title.clone(),
+                time: time::Duration::ZERO,
+                completed: false,
+                sessions: 0,
+            });
+            stats.time += record.time;
+            stats.completed |= record.completed;
+            stats.sessions += 1;
+        }
+        maps.into_values().collect()
+    }
+}
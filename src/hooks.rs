@@ -0,0 +1,105 @@
+use std::ffi::OsString;
+use std::fs::File;
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::instance::Instance;
+use crate::session::SessionOutcome;
+
+// Hook commands are user-written shell snippets, so unlike the game itself they do go through `sh -c`
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct Hooks {
+    #[serde(default)]
+    pub pre_launch: Vec<String>,
+    #[serde(default)]
+    pub post_exit: Vec<String>,
+}
+
+impl Hooks {
+    pub fn is_empty(&self) -> bool {
+        self.pre_launch.is_empty() && self.post_exit.is_empty()
+    }
+
+    // Global hooks wrap the instance's ones: global pre → instance pre → game → instance post → global post
+    pub fn wrapped_by(&self, global: &Hooks) -> Hooks {
+        Hooks {
+            pre_launch: global.pre_launch.iter().chain(&self.pre_launch).cloned().collect(),
+            post_exit: self.post_exit.iter().chain(&global.post_exit).cloned().collect(),
+        }
+    }
+
+    // Stops at the first failing hook, the game must not be started after that
+    pub fn run_pre_launch(&self, env: &[(String, OsString)], log: Option<&File>) -> Result<()> {
+        for command in &self.pre_launch {
+            run_hook(command, env, log).context("Pre-launch hook failed, not starting the game")?;
+        }
+        Ok(())
+    }
+
+    // Every hook runs even if an earlier one failed, the failures are returned
+    pub fn run_post_exit(&self, env: &[(String, OsString)], log: Option<&File>) -> Vec<anyhow::Error> {
+        self.post_exit
+            .iter()
+            .filter_map(|command| run_hook(command, env, log).err())
+            .collect()
+    }
+}
+
+pub fn get_hook_env(instance: &Instance, outcome: Option<SessionOutcome>) -> Vec<(String, OsString)> {
+    let mut env = vec![(
+        "SULPHUR_INSTANCE".to_string(),
+        OsString::from(&instance.metadata.name),
+    )];
+
+    if let Some(savedir) = instance.gamedata.get_absolute_savedir() {
+        env.push(("SULPHUR_SAVEDIR".to_string(), savedir.into_os_string()));
+    }
+
+    if let Some(outcome) = outcome {
+        let (kind, code, signal) = match outcome {
+            SessionOutcome::Clean => ("clean", Some(0), None),
+            SessionOutcome::Error { code } => ("error", Some(code), None),
+            SessionOutcome::Crash { signal, .. } => ("crash", None, Some(signal)),
+            SessionOutcome::Killed => ("killed", None, None),
+            SessionOutcome::Interrupted => ("interrupted", None, None),
+        };
+        env.push(("SULPHUR_OUTCOME".to_string(), OsString::from(kind)));
+        if let Some(code) = code {
+            env.push(("SULPHUR_EXIT_CODE".to_string(), OsString::from(code.to_string())));
+        }
+        if let Some(signal) = signal {
+            env.push(("SULPHUR_SIGNAL".to_string(), OsString::from(signal.to_string())));
+        }
+        if let Some(duration) = instance.metadata.last_session_duration {
+            env.push((
+                "SULPHUR_SESSION_SECONDS".to_string(),
+                OsString::from(duration.as_secs().to_string()),
+            ));
+        }
+    }
+
+    env
+}
+
+fn run_hook(command: &str, env: &[(String, OsString)], log: Option<&File>) -> Result<()> {
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c").arg(command);
+    cmd.envs(env.iter().map(|(key, value)| (key, value)));
+
+    // Hook output ends up in the session log right next to the game's
+    if let Some(log) = log {
+        cmd.stdout(Stdio::from(log.try_clone()?));
+        cmd.stderr(Stdio::from(log.try_clone()?));
+    }
+
+    let status = cmd
+        .status()
+        .with_context(|| format!("Failed to run hook `{}`", command))?;
+
+    if !status.success() {
+        bail!("Hook `{}` exited with {}", command, status);
+    }
+    Ok(())
+}
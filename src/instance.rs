@@ -1,86 +1,206 @@
+use std::collections::BTreeMap;
 use std::ffi::{OsStr, OsString};
 use std::fs;
 use std::fs::File;
 use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::Stdio;
 use std::time;
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
 use serde::{Deserialize, Serialize};
 use zip::{ZipArchive, ZipWriter, write::FileOptions};
 
 use crate::{SaveableDefaultPath, SulphurConfig};
+use crate::brimpkg::{BrimpkgManifest, ConflictStrategy, PackagedFileKind, strip_unsafe_settings, validate_brimpkg};
+use crate::console::OutputCapture;
+use crate::demo::{Demo, DemoInfo, Demodir};
+use crate::engine::Engine;
+use crate::engine_config::ConfigSeed;
+use crate::flatpak::{FilesystemAccess, FlatpakSandbox};
 use crate::game_data::GameData;
+use crate::heartbeat::SessionMarker;
+use crate::history::{SessionHistory, SessionRecord};
+use crate::hooks::{Hooks, get_hook_env};
+use crate::launch::{LaunchPlan, ParamLayer};
+use crate::launch_options::LaunchOptions;
+use crate::logs::SessionLog;
 use crate::metadata::Metadata;
 use crate::traits::{Movable, Saveable};
+use crate::multiplayer::{MultiplayerConfig, NetLaunch, get_asset_fingerprint};
+use crate::utils::{get_absolute_data_path, get_enabled};
 use crate::asset::{Iwad, Mod};
 use crate::savedir::Savedir;
+use crate::screenshot::Screenshot;
+use crate::session::{GameSession, SessionOutcome};
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Instance {
     pub metadata: Metadata,
     pub gamedata: GameData,
+    // Name of an engine registered in `SulphurConfig::engines`, `None` uses the default one
+    #[serde(default)]
+    pub engine: Option<String>,
+    #[serde(default)]
+    pub hooks: Hooks,
+    #[serde(default)]
+    pub multiplayer: MultiplayerConfig,
 }
 
 impl Instance {
     pub const FILENAME: &'static str = "instance.toml";
-    pub fn get_full_command(&self, gzdoom: &OsStr) -> OsString {
-        let mut full_command = OsString::new();
-        full_command.push(gzdoom);
-        full_command.push(" ");
-        full_command.push(self.gamedata.get_parameters());
-        full_command
+
+    // Raw parameters go global → engine → instance → per-launch, so later layers win
+    // for engines where the last occurrence of a flag counts. Only `SulphurConfig` knows the
+    // global layer and hooks, go through `SulphurConfig::get_launch_plan` instead
+    pub(crate) fn get_layered_launch_plan(
+        &self,
+        engine: &Engine,
+        global_params: &[OsString],
+        overrides: &LaunchOptions,
+    ) -> Result<LaunchPlan> {
+        let options = self.gamedata.launch_options.overridden_by(overrides);
+        let mut plan = match engine.get_flatpak_app() {
+            Some(app_id) => {
+                let sandbox = FlatpakSandbox::new(app_id, self.get_sandbox_paths());
+                LaunchPlan::new_flatpak(&engine.command, sandbox)
+            }
+            None => LaunchPlan::new(&engine.command),
+        };
+        plan.args(self.gamedata.get_parameters(engine.get_port(), &options)?);
+        if let Some(screenshot_args) = self
+            .get_screenshot_dir()
+            .ok()
+            .and_then(|dir| engine.get_port().get_screenshot_arguments(&dir))
+        {
+            plan.args(screenshot_args);
+        }
+        plan.layer_args(ParamLayer::Global, global_params);
+        plan.layer_args(ParamLayer::Engine, &engine.additional_params);
+        plan.layer_args(ParamLayer::Instance, &self.gamedata.additional_params);
+        plan.layer_args(ParamLayer::Launch, &options.extra_params);
+        plan.env = self.gamedata.environment.clone();
+        plan.current_dir = self.gamedata.get_absolute_working_dir();
+        plan.hooks = self.hooks.clone();
+        plan.engine_name = Some(engine.get_display_name());
+        Ok(plan)
     }
 
-    pub fn run(&mut self, full_command: OsString) {
-        self.metadata.last_played = Some(time::SystemTime::now());
+    // Everything a sandboxed engine has to be able to reach for this instance
+    pub fn get_sandbox_paths(&self) -> Vec<FilesystemAccess> {
+        let mut paths = Vec::new();
+
+        if let Some(savedir) = self.gamedata.get_absolute_savedir() {
+            paths.push(FilesystemAccess::read_write(savedir));
+        }
+
+        if let Ok(demodir) = self.get_demodir() {
+            paths.push(FilesystemAccess::read_write(demodir));
+        }
+
+        if let Ok(screenshot_dir) = self.get_screenshot_dir() {
+            paths.push(FilesystemAccess::read_write(screenshot_dir));
+        }
 
+        // The whole dir, engines save their config through a temporary file next to it. It also
+        // keeps every writable entry a directory, see `FlatpakSandbox::create_writable_dirs`
+        if let Some(config_dir) = self
+            .gamedata
+            .get_absolute_config_file()
+            .and_then(|config_file| config_file.parent().map(Path::to_path_buf))
         {
-            // ↓ this really does nothing rn, but it'll turn useful as soon as xdg starts supporting windows
-            #[cfg(target_family = "windows")]
-            {
-                let mut cmd = Command::new("cmd");
-                cmd.arg("/C");
-                cmd
-            }
-            #[cfg(not(target_family = "windows"))]
-            {
-                let mut cmd = Command::new("sh");
-                cmd.arg("-c");
-                cmd
-            }
+            paths.push(FilesystemAccess::read_write(config_dir));
         }
-        .arg(full_command)
-        .output()
-        .expect("failed to execute process");
 
-        if let Some(t) = self.metadata.last_played {
-            self.metadata.last_session_duration = t.elapsed().ok();
+        if let Some(working_dir) = self.gamedata.get_absolute_working_dir() {
+            paths.push(FilesystemAccess::read_write(working_dir));
         }
 
-        if let Some(t) = self.metadata.last_session_duration {
-            self.metadata.playtime += t;
+        let iwads = get_enabled(&self.gamedata.iwads).filter_map(|iwad| iwad.get_absolute_path());
+        let mods = get_enabled(&self.gamedata.mods).filter_map(|mod_asset| mod_asset.get_absolute_path());
+        paths.extend(iwads.chain(mods).map(FilesystemAccess::read_only));
+
+        paths
+    }
+
+    pub fn set_engine(&mut self, engine: Option<String>) {
+        self.engine = engine;
+    }
+
+    pub fn launch(&mut self, plan: &LaunchPlan) -> Result<GameSession> {
+        // This session's marker would replace one that was never recovered, losing that session
+        self.recover_interrupted_session()
+            .context("Failed to recover the previous session, not starting the game")?;
+        let marker_path = self.get_session_marker_path()?;
+        if marker_path.is_file() {
+            bail!("{} is already running", self.metadata.name);
         }
+
+        let (log_path, log_file) = SessionLog::create_in(&self.get_logs_dir()?)?;
+        fs::create_dir_all(self.get_screenshot_dir()?)?;
+        if let Some(sandbox) = &plan.sandbox {
+            sandbox.create_writable_dirs()?;
+        }
+        plan.hooks.run_pre_launch(&get_hook_env(self, None), Some(&log_file))?;
+
+        let start_time = time::SystemTime::now();
+        let mut child = plan
+            .get_command()
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Failed to start {}", plan.program.to_string_lossy()))?;
+        let capture = OutputCapture::start(&mut child, log_file);
+
+        self.metadata.last_played = Some(start_time);
+        let mut session = GameSession::new(child, start_time, log_path, plan);
+        session.set_output_capture(capture);
+        session.set_assets(
+            get_enabled(&self.gamedata.iwads).map(|iwad| iwad.as_ref().path.clone()).collect(),
+            get_enabled(&self.gamedata.mods).map(|mod_asset| mod_asset.as_ref().path.clone()).collect(),
+        );
+        session.start_heartbeat(marker_path);
+        Ok(session)
+    }
+
+    pub fn run(&mut self, plan: &LaunchPlan) -> Result<SessionOutcome> {
+        self.launch(plan)?.wait(self)
     }
 
     pub fn save_brimpkg(&self, path: &Path, transfer_saves: bool, transfer_playtime: bool) -> Result<File> {
         let file = File::create(path)?;
         let mut zip = ZipWriter::new(file);
         let mut new_instance = self.clone();
+        let mut manifest = BrimpkgManifest::new();
 
         for dirname in [Mod::get_dir_name(), Iwad::get_dir_name()] {
             zip.add_directory(dirname, FileOptions::default())?;
         }
 
+        // Hooks and most environment variables are for this machine only, the importing side
+        // drops them anyway
+        strip_unsafe_settings(&mut new_instance);
         new_instance.initialize_relative_savedir()?;
         if transfer_saves {
-            let saves_dir = self.gamedata.savedir.clone();
+            // The savedir is usually relative to the sulphur data dir
+            let saves_dir = self
+                .gamedata
+                .get_absolute_savedir()
+                .context("Failed to determine savedir")?;
             let new_saves_dir = new_instance.gamedata.get_savedir().to_string_lossy();
             zip.add_directory(new_saves_dir.to_string(), FileOptions::default())?;
-            for entry in fs::read_dir(&saves_dir)? {
-                let entry = entry?;
+            // Nothing saved yet, nothing to transfer
+            let entries = match fs::read_dir(&saves_dir) {
+                Ok(entries) => entries.collect::<std::io::Result<Vec<_>>>()?,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => vec![],
+                Err(e) => return Err(e).with_context(|| format!("Failed to read {}", saves_dir.display())),
+            };
+            for entry in entries {
                 let path = entry.path();
+                // Some engines keep subfolders in there, only plain save files get packaged
+                if !path.is_file() {
+                    continue;
+                }
                 let filename = path.file_name().unwrap();
                 let filename = filename.to_string_lossy();
                 let dest_path = format!("{}/{}", &new_saves_dir, filename);
@@ -88,6 +208,7 @@ impl Instance {
                 let mut buffer = Vec::new();
                 File::open(&path)?.read_to_end(&mut buffer)?;
                 zip.write_all(&buffer)?;
+                manifest.add_file(dest_path, PackagedFileKind::Save, &buffer);
             }
         }
 
@@ -95,18 +216,39 @@ impl Instance {
             new_instance.metadata.playtime = time::Duration::new(0, 0);
             new_instance.metadata.last_played = None;
             new_instance.metadata.last_session_duration = None;
+            new_instance.metadata.last_outcome = None;
+        }
+
+        if let Some(config_file) = self.gamedata.get_absolute_config_file() {
+            let filename = config_file.file_name().context("Config file name is missing")?;
+            let relative_path = new_instance.get_relative_data_dir().join(filename);
+            let content = fs::read(&config_file)?;
+            zip.start_file(relative_path.to_string_lossy(), FileOptions::default())?;
+            zip.write_all(&content)?;
+            manifest.add_file(relative_path.to_string_lossy().into_owned(), PackagedFileKind::Config, &content);
+            new_instance.gamedata.config_file = Some(relative_path);
         }
 
-        write_assets_to_zip(&mut zip, new_instance.gamedata.mods.as_mut_slice())?;
-        write_assets_to_zip(&mut zip, new_instance.gamedata.iwads.as_mut_slice())?;
+        // A working dir outside the data dir only exists on this machine
+        if let Some(working_dir) = self.gamedata.get_absolute_working_dir() {
+            let data_dir = SulphurConfig::get_dir().place_data_file("")?;
+            new_instance.gamedata.working_dir = working_dir.strip_prefix(&data_dir).ok().map(Path::to_path_buf);
+        }
+
+        write_assets_to_zip(&mut zip, &mut manifest, PackagedFileKind::Mod, new_instance.gamedata.mods.as_mut_slice())?;
+        write_assets_to_zip(&mut zip, &mut manifest, PackagedFileKind::Iwad, new_instance.gamedata.iwads.as_mut_slice())?;
 
         zip.start_file(Self::FILENAME, FileOptions::default())?;
         zip.write_all(new_instance.as_toml()?.as_bytes())?;
+        zip.start_file(BrimpkgManifest::FILENAME, FileOptions::default())?;
+        zip.write_all(manifest.as_toml()?.as_bytes())?;
 
         Ok(zip.finish()?)
     }
 
-    pub fn load_brimpkg(path: &Path) -> Result<Self> {
+    // `conflicts` decides what happens to mods and IWADs that exist under the same name with
+    // different content, see `SulphurConfig::import_conflicts`
+    pub fn load_brimpkg(path: &Path, conflicts: ConflictStrategy) -> Result<Self> {
         let file = File::open(path)?;
         let mut archive = ZipArchive::new(file)?;
 
@@ -118,50 +260,280 @@ impl Instance {
             instance_file.read_to_string(&mut instance_toml_content)?;
         }
 
-        let mut instance: Instance = Self::from_toml(instance_toml_content)?;
+        // Older packages get upgraded instead of failing to deserialize
+        let manifest = BrimpkgManifest::read_from(&mut archive)?;
+        let mut instance = manifest.migrate_instance(instance_toml_content)?;
+        // Packages from before working dirs got relativized on export can still carry one from
+        // another machine, which is meaningless here anyway
+        if instance.gamedata.working_dir.as_ref().is_some_and(|dir| dir.is_absolute()) {
+            instance.gamedata.working_dir = None;
+        }
+        // Never trusted from a package, whoever imports it can add their own afterwards
+        for dropped in strip_unsafe_settings(&mut instance) {
+            println!("ignoring {} from brimpkg...", dropped);
+        }
+        // Nothing gets written unless every entry is safe to extract and intact
+        validate_brimpkg(&mut archive, &instance)?;
+        manifest.verify(&mut archive)?;
 
+        let data_dir = SulphurConfig::get_dir().place_data_file("")?;
+        // Archive entry → where its content ended up, relative to the data dir
+        let mut placed = BTreeMap::new();
         for i in 0..archive.len() {
             let mut file = archive.by_index(i)?;
             let file_path = PathBuf::from(file.name());
 
-            if file.is_dir() || file.name() == Self::FILENAME {
+            if file.is_dir() || BrimpkgManifest::is_packaged(file.name()) {
                 continue;
             }
             println!("loading {} from zip...", &file_path.display());
 
-            if let Some(parent) = file_path.parent() {
-                if let Some(filename) = file_path.file_name() {
-                    let dest_dir = SulphurConfig::get_dir().place_data_file(parent)?;
-                    let dest_path = dest_dir.join(filename);
-                    fs::create_dir_all(dest_dir)?;
-
-                    // Skip if file already exists
-                    if !dest_path.exists() {
-                        let mut dest_file = File::create(&dest_path)?;
-                        let mut buffer = Vec::new();
-                        file.read_to_end(&mut buffer)?;
-                        Write::write_all(&mut dest_file, &buffer)?;
-                    } else {
-                        println!("{} found in Sulphur directory, skipping...", &dest_path.display());
-                    }
-                }
+            // Saves and configs belong to the instance, an existing one is never replaced
+            let strategy = match PackagedFileKind::from_entry_name(file.name()) {
+                Some(PackagedFileKind::Iwad | PackagedFileKind::Mod) => conflicts,
+                _ => ConflictStrategy::KeepExisting,
+            };
+            let mut buffer = Vec::new();
+            file.read_to_end(&mut buffer)?;
+            let dest_path = strategy.place_file(&data_dir, &file_path, &buffer)?;
+            if dest_path != file_path {
+                println!(
+                    "{} differs from the one in Sulphur directory, stored as {}...",
+                    file_path.display(),
+                    dest_path.display()
+                );
             }
+            placed.insert(file_path, dest_path);
         }
         // Update asset paths to point to the extracted files
         for iwad in &mut instance.gamedata.iwads {
             if let Some(filename) = iwad.get_filename() {
-                iwad.as_mut().path = Iwad::get_relative_path(filename);
+                let packaged = Iwad::get_relative_path(filename);
+                iwad.as_mut().path = placed.get(&packaged).cloned().unwrap_or(packaged);
             }
         }
         for mod_asset in &mut instance.gamedata.mods {
             if let Some(filename) = mod_asset.get_filename() {
-                mod_asset.as_mut().path = Mod::get_relative_path(filename);
+                let packaged = Mod::get_relative_path(filename);
+                mod_asset.as_mut().path = placed.get(&packaged).cloned().unwrap_or(packaged);
             }
         }
 
         Ok(instance)
     }
 
+    pub fn get_data_dir(&self) -> Result<PathBuf> {
+        Ok(Self::get_full_dir()?.join(&self.metadata.name))
+    }
+
+    pub fn get_relative_data_dir(&self) -> PathBuf {
+        Self::get_relative_path(OsStr::new(&self.metadata.name))
+    }
+
+    // Gives the instance its own engine config so settings and binds don't leak between instances
+    pub fn create_config_file(&mut self, engine: &Engine, seed: ConfigSeed) -> Result<PathBuf> {
+        // The file would never reach the engine
+        if engine.get_port().get_config_prefix().is_none() {
+            bail!("{} can't be given a config file", engine.get_port().get_name());
+        }
+        let filename = format!("config.{}", engine.get_port().get_config_extension());
+        let relative_path = self.get_relative_data_dir().join(filename);
+        let absolute_path = get_absolute_data_path(&relative_path).context("Failed to determine config path")?;
+
+        seed.write_to(&absolute_path, engine)?;
+        self.gamedata.config_file = Some(relative_path);
+        Ok(absolute_path)
+    }
+
+    // Goes back to the engine's global config, the file itself is deleted
+    pub fn remove_config_file(&mut self) -> Result<()> {
+        if let Some(path) = self.gamedata.get_absolute_config_file()
+            && path.is_file()
+        {
+            fs::remove_file(&path)?;
+        }
+        self.gamedata.config_file = None;
+        Ok(())
+    }
+
+    pub fn get_history_path(&self) -> Result<PathBuf> {
+        Ok(self.get_data_dir()?.join(SessionHistory::FILENAME))
+    }
+
+    pub fn get_history(&self) -> Result<SessionHistory> {
+        SessionHistory::load_or_default(&self.get_history_path()?)
+    }
+
+    pub fn get_session_marker_path(&self) -> Result<PathBuf> {
+        Ok(self.get_data_dir()?.join(SessionMarker::FILENAME))
+    }
+
+    // Credits a session the launcher never saw finish, e.g. because it was killed or the machine
+    // lost power. Returns `None` if there's nothing to recover or the game is still running
+    pub fn recover_interrupted_session(&mut self) -> Result<Option<SessionRecord>> {
+        let marker_path = self.get_session_marker_path()?;
+        let marker = match SessionMarker::load_if_exists(&marker_path)? {
+            Some(marker) if !marker.is_game_running() => marker,
+            _ => return Ok(None),
+        };
+
+        let record = marker.into_record();
+        SessionHistory::append_to(&self.get_history_path()?, record.clone())
+            .context("Failed to record interrupted session")?;
+        fs::remove_file(&marker_path)
+            .with_context(|| format!("Failed to remove {}", marker_path.display()))?;
+
+        self.metadata.last_played = Some(record.start);
+        self.metadata.last_session_duration = Some(record.duration);
+        self.metadata.last_outcome = Some(record.outcome);
+        self.metadata.playtime += record.duration;
+        Ok(Some(record))
+    }
+
+    pub fn get_logs_dir(&self) -> Result<PathBuf> {
+        Ok(self.get_data_dir()?.join(SessionLog::DIR_NAME))
+    }
+
+    pub fn get_logs(&self) -> Result<Vec<SessionLog>> {
+        SessionLog::list(&self.get_logs_dir()?)
+    }
+
+    pub fn get_last_log(&self) -> Result<Option<SessionLog>> {
+        Ok(self.get_logs()?.into_iter().next())
+    }
+
+    // Last lines of the most recent log, if the last session ended in a crash
+    pub fn get_last_crash_log(&self) -> Result<Option<Vec<String>>> {
+        if !self.metadata.last_outcome.is_some_and(|outcome| outcome.is_crash()) {
+            return Ok(None);
+        }
+        match self.get_last_log()? {
+            Some(log) => Ok(Some(log.tail(GameSession::CRASH_LOG_LINES)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_screenshot_dir(&self) -> Result<PathBuf> {
+        Ok(self.get_data_dir()?.join(Screenshot::DIR_NAME))
+    }
+
+    pub fn get_screenshots(&self) -> Result<Vec<Screenshot>> {
+        Screenshot::list(&self.get_screenshot_dir()?)
+    }
+
+    // Stored relative to the sulphur data dir when possible, so the image survives moving it
+    pub fn set_image_from_screenshot(&mut self, screenshot: &Screenshot) {
+        let relative = get_absolute_data_path(Path::new(""))
+            .and_then(|base| screenshot.path.strip_prefix(base).ok().map(Path::to_path_buf));
+        self.metadata.image = Some(relative.unwrap_or_else(|| screenshot.path.clone()));
+    }
+
+    pub fn get_absolute_image(&self) -> Option<PathBuf> {
+        self.metadata.image.as_deref().and_then(get_absolute_data_path)
+    }
+
+    pub fn get_demodir(&self) -> Result<PathBuf> {
+        Ok(Demodir::get()?.join(&self.metadata.name))
+    }
+
+    pub fn get_demos(&self) -> Result<Vec<Demo>> {
+        Demo::list(&self.get_demodir()?)
+    }
+
+    // Picks a fresh file name in the demo dir and stores which assets are loaded next to it,
+    // pass the returned options to `SulphurConfig::get_launch_plan` to record
+    pub fn prepare_demo_recording(&self) -> Result<LaunchOptions> {
+        let demodir = self.get_demodir()?;
+        fs::create_dir_all(&demodir)?;
+
+        let recorded = time::SystemTime::now();
+        let secs = recorded
+            .duration_since(time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let path = demodir.join(format!("{}-{}.{}", self.metadata.name, secs, Demo::EXTENSION));
+
+        let info = DemoInfo {
+            recorded,
+            engine: self.engine.clone(),
+            iwads: get_enabled(&self.gamedata.iwads).map(|iwad| iwad.as_ref().path.clone()).collect(),
+            mods: get_enabled(&self.gamedata.mods).map(|mod_asset| mod_asset.as_ref().path.clone()).collect(),
+        };
+        info.save_as(Demo::get_info_path(&path))?;
+
+        Ok(LaunchOptions {
+            record: Some(path),
+            ..Default::default()
+        })
+    }
+
+    // Host and join options are both built from this instance's own asset list, so two players
+    // launching the same instance always load the same files in the same order
+    pub fn get_host_options(&self) -> Result<LaunchOptions> {
+        self.multiplayer.validate()?;
+        let config = &self.multiplayer;
+        Ok(LaunchOptions {
+            net: Some(NetLaunch::Host {
+                players: config.players,
+                netmode: config.netmode,
+                game_mode: config.game_mode,
+                port: config.port,
+            }),
+            ..Default::default()
+        })
+    }
+
+    // The given address is remembered for next time. `expected_fingerprint` is the host's
+    // `get_asset_fingerprint`, joining fails if this instance's assets don't match it
+    pub fn get_join_options(
+        &mut self,
+        address: Option<String>,
+        expected_fingerprint: Option<&str>,
+    ) -> Result<LaunchOptions> {
+        self.multiplayer.validate()?;
+        if let Some(expected) = expected_fingerprint {
+            let fingerprint = self.get_asset_fingerprint();
+            if fingerprint != expected {
+                bail!(
+                    "Asset fingerprint {} doesn't match the host's {}, both sides need the same IWADs \
+                     and mods in the same order",
+                    fingerprint,
+                    expected
+                );
+            }
+        }
+        if address.is_some() {
+            self.multiplayer.address = address;
+        }
+        let address = self.multiplayer.address.clone().context("No address to join")?;
+        Ok(LaunchOptions {
+            net: Some(NetLaunch::Join {
+                address,
+                port: self.multiplayer.port,
+            }),
+            ..Default::default()
+        })
+    }
+
+    // Compare between players before a netgame, mismatching assets desync immediately. Only
+    // names and sizes go in, so it's cheap but won't catch two different files of the same size
+    pub fn get_asset_fingerprint(&self) -> String {
+        let iwads = get_enabled(&self.gamedata.iwads).map(|iwad| iwad.as_ref());
+        let mods = get_enabled(&self.gamedata.mods).map(|mod_asset| mod_asset.as_ref());
+        let assets: Vec<(String, u64)> = iwads
+            .chain(mods)
+            .map(|asset| {
+                let name = asset.path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+                let size = get_absolute_data_path(&asset.path)
+                    .and_then(|path| fs::metadata(path).ok())
+                    .map(|m| m.len())
+                    .unwrap_or(0);
+                (name, size)
+            })
+            .collect();
+        get_asset_fingerprint(assets.iter().map(|(name, size)| (name.as_str(), *size)))
+    }
+
     pub fn create_savedir(&self) -> std::io::Result<()> {
         fs::create_dir_all(self.gamedata.savedir.clone())
     }
@@ -181,7 +553,12 @@ impl Instance {
     }
 }
 
-fn write_assets_to_zip<T>(zip: &mut ZipWriter<File>, assets: &mut [T]) -> Result<()>
+fn write_assets_to_zip<T>(
+    zip: &mut ZipWriter<File>,
+    manifest: &mut BrimpkgManifest,
+    kind: PackagedFileKind,
+    assets: &mut [T],
+) -> Result<()>
 where
     T: Movable + AsMut<crate::asset::Asset> + AsRef<crate::asset::Asset>,
 {
@@ -193,15 +570,14 @@ where
             .get_absolute_path()
             .ok_or_else(|| anyhow::anyhow!("Asset path is invalid"))?;
 
-        {
-            let zip_path = format!("{}", T::get_relative_path(filename).to_string_lossy());
-            println!("Writing {} to zip...", &zip_path);
-            zip.start_file(zip_path, FileOptions::default())?;
-        }
+        let zip_path = format!("{}", T::get_relative_path(filename).to_string_lossy());
+        println!("Writing {} to zip...", &zip_path);
+        zip.start_file(&zip_path, FileOptions::default())?;
         asset.as_mut().path = T::get_relative_path(filename);
 
         let content = fs::read(&absolute_path)?;
         zip.write_all(&content)?;
+        manifest.add_file(zip_path, kind, &content);
     }
     Ok(())
 }
@@ -0,0 +1,242 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::path::PathBuf;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::process::Command;
+
+use shlex::bytes::Quoter;
+
+use crate::flatpak::{self, FlatpakSandbox};
+use crate::hooks::Hooks;
+
+// Where an argument came from, in the order the layers end up on the command line
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum ParamLayer {
+    // Words from the engine command itself and Flatpak overrides
+    Command,
+    // Assets, savedir and typed launch options
+    Generated,
+    Global,
+    Engine,
+    Instance,
+    Launch,
+}
+
+impl ParamLayer {
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            ParamLayer::Command => "command",
+            ParamLayer::Generated => "generated",
+            ParamLayer::Global => "global",
+            ParamLayer::Engine => "engine",
+            ParamLayer::Instance => "instance",
+            ParamLayer::Launch => "launch",
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct LaunchArg {
+    pub value: OsString,
+    pub layer: ParamLayer,
+}
+
+#[derive(Clone)]
+pub struct LaunchPlan {
+    pub program: OsString,
+    pub args: Vec<LaunchArg>,
+    pub env: BTreeMap<String, String>,
+    pub current_dir: Option<PathBuf>,
+    pub sandbox: Option<FlatpakSandbox>,
+    pub hooks: Hooks,
+    // Recorded in the session history
+    pub engine_name: Option<String>,
+}
+
+impl LaunchPlan {
+    // `command` may carry its own arguments (e.g. `flatpak run org.zdoom.GZDoom`),
+    // so it's split the same way a shell would before anything else is appended
+    pub fn new(command: &OsStr) -> Self {
+        let mut words = split_command(command).into_iter();
+        let mut plan = Self::with_program(expand_home(words.next().unwrap_or_default()));
+        plan.layer_args(ParamLayer::Command, words);
+        plan
+    }
+
+    // The `--filesystem` overrides have to go between `flatpak run` and the app id,
+    // anything the command had after the app id is kept after it
+    pub fn new_flatpak(command: &OsStr, sandbox: FlatpakSandbox) -> Self {
+        let words = split_command(command);
+        let app_position = match flatpak::parse_app_id(command) {
+            Some(app_id) if app_id == sandbox.app_id => words.iter().position(|word| *word == *app_id),
+            _ => None,
+        };
+
+        let mut plan = match app_position {
+            Some(i) => {
+                let mut plan = Self::with_program(expand_home(words[0].clone()));
+                plan.layer_args(ParamLayer::Command, &words[1..i]);
+                plan
+            }
+            None => {
+                let mut plan = Self::with_program(OsString::from("flatpak"));
+                plan.layer_args(ParamLayer::Command, ["run"]);
+                plan
+            }
+        };
+
+        plan.layer_args(ParamLayer::Command, sandbox.get_arguments());
+        plan.layer_args(ParamLayer::Command, [&sandbox.app_id]);
+        if let Some(i) = app_position {
+            plan.layer_args(ParamLayer::Command, &words[i + 1..]);
+        }
+        plan.sandbox = Some(sandbox);
+        plan
+    }
+
+    fn with_program(program: OsString) -> Self {
+        Self {
+            program,
+            args: Vec::new(),
+            env: BTreeMap::new(),
+            current_dir: None,
+            sandbox: None,
+            hooks: Hooks::default(),
+            engine_name: None,
+        }
+    }
+
+    pub fn arg<S: AsRef<OsStr>>(&mut self, arg: S) -> &mut Self {
+        self.layer_args(ParamLayer::Generated, [arg])
+    }
+
+    pub fn args<I, S>(&mut self, args: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.layer_args(ParamLayer::Generated, args)
+    }
+
+    pub fn layer_args<I, S>(&mut self, layer: ParamLayer, args: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.args.extend(args.into_iter().map(|arg| LaunchArg {
+            value: arg.as_ref().to_os_string(),
+            layer,
+        }));
+        self
+    }
+
+    pub fn get_args(&self) -> impl Iterator<Item = &OsString> {
+        self.args.iter().map(|arg| &arg.value)
+    }
+
+    pub fn get_layer_args(&self, layer: ParamLayer) -> impl Iterator<Item = &OsString> {
+        self.args
+            .iter()
+            .filter(move |arg| arg.layer == layer)
+            .map(|arg| &arg.value)
+    }
+
+    pub fn get_command(&self) -> Command {
+        let mut cmd = Command::new(&self.program);
+        cmd.args(self.get_args());
+        // `flatpak run` forwards the environment into the sandbox, so this works for both
+        cmd.envs(&self.env);
+        if let Some(dir) = &self.current_dir {
+            cmd.current_dir(dir);
+        }
+        cmd
+    }
+
+    // Only meant for showing the user / copy-pasting into a terminal, `run` never goes through a shell
+    pub fn to_shell_string(&self) -> OsString {
+        let command = quote_words(std::iter::once(&self.program).chain(self.get_args()).map(|word| word.as_bytes()));
+        if self.env.is_empty() {
+            return OsString::from_vec(command);
+        }
+
+        // `KEY=value gzdoom` only works if the shell recognizes every word as an assignment, for
+        // anything else `env` takes them as plain arguments
+        let mut line = if self.env.keys().all(|key| is_shell_identifier(key)) {
+            let assignments: Vec<Vec<u8>> = self
+                .env
+                .iter()
+                .map(|(key, value)| [format!("{}=", key).as_bytes(), &quote_words([value.as_bytes()])].concat())
+                .collect();
+            assignments.join(&b' ')
+        } else {
+            let assignments: Vec<String> = self.env.iter().map(|(key, value)| format!("{}={}", key, value)).collect();
+            quote_words(std::iter::once("env").chain(assignments.iter().map(String::as_str)).map(str::as_bytes))
+        };
+        line.push(b' ');
+        line.extend(command);
+        OsString::from_vec(line)
+    }
+
+    // Consecutive arguments grouped by the layer they came from, each group shell-quoted
+    pub fn to_layered_preview(&self) -> Vec<(ParamLayer, OsString)> {
+        let mut preview: Vec<(ParamLayer, Vec<&[u8]>)> = Vec::new();
+        for arg in &self.args {
+            match preview.last_mut() {
+                Some((layer, words)) if *layer == arg.layer => words.push(arg.value.as_bytes()),
+                _ => preview.push((arg.layer, vec![arg.value.as_bytes()])),
+            }
+        }
+        preview
+            .into_iter()
+            .map(|(layer, words)| (layer, OsString::from_vec(quote_words(words))))
+            .collect()
+    }
+}
+
+// Commands used to go through `sh -c`, so stored ones like `~/bin/gzdoom` or `$HOME/games/gzdoom`
+// still need their home dir expanded now that there's no shell
+fn expand_home(program: OsString) -> OsString {
+    let Some(home) = env::var_os("HOME") else {
+        return program;
+    };
+    let bytes = program.as_bytes();
+    let rest = ["~", "$HOME", "${HOME}"].iter().find_map(|prefix| {
+        let rest = bytes.strip_prefix(prefix.as_bytes())?;
+        (rest.is_empty() || rest.starts_with(b"/")).then_some(rest)
+    });
+
+    match rest {
+        Some(rest) => {
+            let mut expanded = home.into_vec();
+            expanded.extend_from_slice(rest);
+            OsString::from_vec(expanded)
+        }
+        None => program,
+    }
+}
+
+fn is_shell_identifier(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn quote_words<'a>(words: impl IntoIterator<Item = &'a [u8]>) -> Vec<u8> {
+    Quoter::new()
+        .allow_nul(true)
+        .join(words)
+        .unwrap_or_default()
+}
+
+pub fn split_command(command: &OsStr) -> Vec<OsString> {
+    match shlex::bytes::split(command.as_bytes()) {
+        Some(words) => words.into_iter().map(OsString::from_vec).collect(),
+        // Unbalanced quotes, fall back to plain whitespace splitting
+        None => command
+            .as_bytes()
+            .split(|b| b.is_ascii_whitespace())
+            .filter(|word| !word.is_empty())
+            .map(|word| OsStr::from_bytes(word).to_os_string())
+            .collect(),
+    }
+}
@@ -0,0 +1,155 @@
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::multiplayer::{MultiplayerConfig, NetLaunch};
+use crate::source_port::SourcePort;
+
+// Every field is optional so the same struct works both as an instance's defaults
+// and as a per-launch override on top of them
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Eq, Debug)]
+#[serde(default)]
+pub struct LaunchOptions {
+    pub skill: Option<u8>,
+    // Map lump name, e.g. `MAP07` or `E2M4`
+    pub warp: Option<String>,
+    pub nomonsters: Option<bool>,
+    pub fast: Option<bool>,
+    pub respawn: Option<bool>,
+    pub nomusic: Option<bool>,
+    pub nosound: Option<bool>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub record: Option<PathBuf>,
+    pub playdemo: Option<PathBuf>,
+    pub net: Option<NetLaunch>,
+    // One-off raw parameters, they're the last layer on the command line. Never stored,
+    // the instance's `additional_params` is the persistent equivalent
+    #[serde(skip)]
+    pub extra_params: Vec<OsString>,
+}
+
+impl LaunchOptions {
+    pub const SKILL_RANGE: std::ops::RangeInclusive<u8> = 1..=5;
+
+    pub fn overridden_by(&self, overrides: &LaunchOptions) -> LaunchOptions {
+        LaunchOptions {
+            skill: overrides.skill.or(self.skill),
+            warp: overrides.warp.clone().or_else(|| self.warp.clone()),
+            nomonsters: overrides.nomonsters.or(self.nomonsters),
+            fast: overrides.fast.or(self.fast),
+            respawn: overrides.respawn.or(self.respawn),
+            nomusic: overrides.nomusic.or(self.nomusic),
+            nosound: overrides.nosound.or(self.nosound),
+            width: overrides.width.or(self.width),
+            height: overrides.height.or(self.height),
+            record: overrides.record.clone().or_else(|| self.record.clone()),
+            playdemo: overrides.playdemo.clone().or_else(|| self.playdemo.clone()),
+            net: overrides.net.clone().or_else(|| self.net.clone()),
+            extra_params: self
+                .extra_params
+                .iter()
+                .chain(&overrides.extra_params)
+                .cloned()
+                .collect(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if let Some(skill) = self.skill
+            && !Self::SKILL_RANGE.contains(&skill)
+        {
+            bail!("Skill level {} is out of range", skill);
+        }
+        if let Some(warp) = &self.warp
+            && (warp.is_empty() || warp.contains(char::is_whitespace))
+        {
+            bail!("Invalid map name `{}`", warp);
+        }
+        if self.width == Some(0) || self.height == Some(0) {
+            bail!("Resolution must not be zero");
+        }
+        if self.record.is_some() && self.playdemo.is_some() {
+            bail!("Can't record and play back a demo at the same time");
+        }
+        if let Some(NetLaunch::Host { players, .. }) = &self.net
+            && !MultiplayerConfig::PLAYER_RANGE.contains(players)
+        {
+            bail!("Player count {} is out of range", players);
+        }
+        Ok(())
+    }
+
+    pub fn get_arguments(&self, port: &dyn SourcePort) -> Result<Vec<OsString>> {
+        self.validate()?;
+        let mut arguments = Vec::new();
+
+        if let Some(skill) = self.skill {
+            arguments.extend([OsString::from("-skill"), OsString::from(skill.to_string())]);
+        }
+
+        if let Some(warp) = &self.warp {
+            match port.get_warp_arguments(warp) {
+                Some(warp_arguments) => arguments.extend(warp_arguments),
+                None => bail!("{} can't warp to {}", port.get_name(), warp),
+            }
+        }
+
+        let flags = [
+            (self.nomonsters, "-nomonsters"),
+            (self.fast, "-fast"),
+            (self.respawn, "-respawn"),
+            (self.nomusic, "-nomusic"),
+            (self.nosound, "-nosound"),
+        ];
+        for (enabled, flag) in flags {
+            if enabled == Some(true) {
+                arguments.push(OsString::from(flag));
+            }
+        }
+
+        if let Some(width) = self.width {
+            arguments.extend([OsString::from("-width"), OsString::from(width.to_string())]);
+        }
+        if let Some(height) = self.height {
+            arguments.extend([OsString::from("-height"), OsString::from(height.to_string())]);
+        }
+
+        if let Some(record) = &self.record {
+            arguments.extend(port.get_record_arguments(record));
+        }
+        if let Some(playdemo) = &self.playdemo {
+            arguments.extend([OsString::from("-playdemo"), playdemo.clone().into_os_string()]);
+        }
+
+        if let Some(net) = &self.net {
+            match port.get_net_arguments(net) {
+                Some(net_arguments) => arguments.extend(net_arguments),
+                None => bail!("{} doesn't support this multiplayer mode", port.get_name()),
+            }
+        }
+
+        Ok(arguments)
+    }
+}
+
+// `-warp` only takes episode/map numbers, so only `ExMy` and `MAPxx` lumps can be reached this way
+pub fn get_vanilla_warp_arguments(map: &str) -> Option<Vec<OsString>> {
+    let upper = map.to_ascii_uppercase();
+
+    if let Some(number) = upper.strip_prefix("MAP") {
+        let number: u8 = number.parse().ok()?;
+        return Some(vec![OsString::from("-warp"), OsString::from(number.to_string())]);
+    }
+
+    let (episode, mission) = upper.strip_prefix('E')?.split_once('M')?;
+    let episode: u8 = episode.parse().ok()?;
+    let mission: u8 = mission.parse().ok()?;
+    Some(vec![
+        OsString::from("-warp"),
+        OsString::from(episode.to_string()),
+        OsString::from(mission.to_string()),
+    ])
+}
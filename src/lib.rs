@@ -1,19 +1,57 @@
 pub mod savedir;
+pub mod demo;
+pub mod screenshot;
 pub mod metadata;
 pub mod asset;
 pub mod traits;
 pub mod game_data;
 pub mod instance;
+pub mod brimpkg;
+pub mod launch;
+pub mod launch_options;
+pub mod logs;
+pub mod console;
+pub mod history;
+pub mod stats;
+pub mod session;
+pub mod heartbeat;
 pub mod utils;
 pub mod sulphur_config;
+pub mod source_port;
+pub mod engine;
+pub mod engine_config;
+pub mod discovery;
+pub mod flatpak;
+pub mod hooks;
+pub mod cvars;
+pub mod multiplayer;
 
 pub use savedir::*;
+pub use demo::*;
+pub use screenshot::*;
 pub use metadata::*;
 pub use asset::*;
 pub use traits::*;
 pub use game_data::*;
 pub use instance::*;
+pub use brimpkg::*;
+pub use launch::*;
+pub use launch_options::*;
+pub use logs::*;
+pub use console::*;
+pub use history::*;
+pub use stats::*;
+pub use session::*;
+pub use heartbeat::*;
 pub use sulphur_config::*;
+pub use source_port::*;
+pub use engine::*;
+pub use engine_config::*;
+pub use discovery::*;
+pub use flatpak::*;
+pub use hooks::*;
+pub use cvars::*;
+pub use multiplayer::*;
 pub use utils::*;
 
 // Not much to see here, just loading stuff from their respective files
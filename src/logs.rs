@@ -0,0 +1,84 @@
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time;
+
+use anyhow::{Context, Result};
+
+#[derive(Clone)]
+pub struct SessionLog {
+    pub path: PathBuf,
+    pub created: Option<time::SystemTime>,
+    pub size: u64,
+}
+
+impl SessionLog {
+    pub const DIR_NAME: &'static str = "logs";
+    pub const EXTENSION: &'static str = "log";
+    pub const KEEP_LAST: usize = 10;
+
+    pub fn from_path(path: PathBuf) -> Self {
+        let metadata = fs::metadata(&path).ok();
+        Self {
+            created: metadata.as_ref().and_then(|m| m.modified().ok()),
+            size: metadata.map(|m| m.len()).unwrap_or(0),
+            path,
+        }
+    }
+
+    pub fn read(&self) -> Result<String> {
+        let content = fs::read(&self.path)
+            .with_context(|| format!("Failed to read from {}", self.path.display()))?;
+        // Mods love printing whatever bytes they want, so don't fail on bad UTF-8
+        Ok(String::from_utf8_lossy(&content).into_owned())
+    }
+
+    pub fn tail(&self, lines: usize) -> Result<Vec<String>> {
+        let content = self.read()?;
+        let all: Vec<&str> = content.lines().collect();
+        let start = all.len().saturating_sub(lines);
+        Ok(all[start..].iter().map(|line| line.to_string()).collect())
+    }
+
+    // Creates a fresh log in `dir` and drops the oldest ones so that at most `KEEP_LAST` remain
+    pub fn create_in(dir: &Path) -> Result<(PathBuf, File)> {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+
+        let millis = time::SystemTime::now()
+            .duration_since(time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let path = dir.join(format!("session-{:016}.{}", millis, Self::EXTENSION));
+        let file = File::create(&path)
+            .with_context(|| format!("Failed to create {}", path.display()))?;
+
+        Self::rotate(dir, Self::KEEP_LAST)?;
+        Ok((path, file))
+    }
+
+    pub fn rotate(dir: &Path, keep: usize) -> Result<()> {
+        for log in Self::list(dir)?.into_iter().skip(keep) {
+            fs::remove_file(&log.path)
+                .with_context(|| format!("Failed to remove {}", log.path.display()))?;
+        }
+        Ok(())
+    }
+
+    // Newest first
+    pub fn list(dir: &Path) -> Result<Vec<SessionLog>> {
+        if !dir.is_dir() {
+            return Ok(vec![]);
+        }
+
+        let mut logs: Vec<SessionLog> = fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == Self::EXTENSION))
+            .map(SessionLog::from_path)
+            .collect();
+
+        // File names embed the creation time with a fixed width, so they sort chronologically
+        logs.sort_by(|a, b| b.path.file_name().cmp(&a.path.file_name()));
+        Ok(logs)
+    }
+}
@@ -2,6 +2,8 @@ use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use std::time;
 
+use crate::session::SessionOutcome;
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Metadata {
     pub name: String,
@@ -9,4 +11,6 @@ pub struct Metadata {
     pub playtime: time::Duration,
     pub last_played: Option<time::SystemTime>,
     pub last_session_duration: Option<time::Duration>,
+    #[serde(default)]
+    pub last_outcome: Option<SessionOutcome>,
 }
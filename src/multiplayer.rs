@@ -0,0 +1,155 @@
+use std::ffi::OsString;
+
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum NetMode {
+    #[default]
+    PeerToPeer,
+    PacketServer,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum GameMode {
+    #[default]
+    Coop,
+    Deathmatch,
+    // Deathmatch 2.0, items respawn
+    AltDeath,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+#[serde(default)]
+pub struct MultiplayerConfig {
+    pub players: u8,
+    pub netmode: NetMode,
+    pub game_mode: GameMode,
+    pub port: Option<u16>,
+    // Last address joined, used when joining without giving one. See `Instance::get_join_options`
+    pub address: Option<String>,
+}
+
+impl Default for MultiplayerConfig {
+    fn default() -> Self {
+        Self {
+            players: 2,
+            netmode: NetMode::default(),
+            game_mode: GameMode::default(),
+            port: None,
+            address: None,
+        }
+    }
+}
+
+impl MultiplayerConfig {
+    pub const PLAYER_RANGE: std::ops::RangeInclusive<u8> = 2..=8;
+
+    pub fn validate(&self) -> Result<()> {
+        if !Self::PLAYER_RANGE.contains(&self.players) {
+            bail!("Player count {} is out of range", self.players);
+        }
+        if self.port == Some(0) {
+            bail!("Port must not be zero");
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+#[serde(tag = "role", rename_all = "snake_case")]
+pub enum NetLaunch {
+    Host {
+        players: u8,
+        netmode: NetMode,
+        game_mode: GameMode,
+        port: Option<u16>,
+    },
+    Join {
+        address: String,
+        port: Option<u16>,
+    },
+}
+
+impl NetLaunch {
+    pub fn get_port(&self) -> Option<u16> {
+        match self {
+            NetLaunch::Host { port, .. } | NetLaunch::Join { port, .. } => *port,
+        }
+    }
+}
+
+// GZDoom and Zandronum style: `-host`/`-join` with separate `-port` and game mode flags
+pub fn get_zdoom_net_arguments(net: &NetLaunch) -> Vec<OsString> {
+    let mut arguments: Vec<OsString> = Vec::new();
+
+    match net {
+        NetLaunch::Host {
+            players,
+            netmode,
+            game_mode,
+            ..
+        } => {
+            arguments.extend(["-host".into(), players.to_string().into()]);
+            let netmode = match netmode {
+                NetMode::PeerToPeer => "0",
+                NetMode::PacketServer => "1",
+            };
+            arguments.extend(["-netmode".into(), netmode.into()]);
+            arguments.extend(get_game_mode_argument(*game_mode));
+        }
+        NetLaunch::Join { address, .. } => {
+            arguments.extend(["-join".into(), address.into()]);
+        }
+    }
+
+    if let Some(port) = net.get_port() {
+        arguments.extend(["-port".into(), port.to_string().into()]);
+    }
+
+    arguments
+}
+
+// Chocolate Doom and Odamex style: a built-in server plus `-connect host:port`
+pub fn get_server_net_arguments(net: &NetLaunch) -> Vec<OsString> {
+    match net {
+        NetLaunch::Host { game_mode, port, .. } => {
+            let mut arguments: Vec<OsString> = vec!["-server".into()];
+            arguments.extend(get_game_mode_argument(*game_mode));
+            if let Some(port) = port {
+                arguments.extend(["-port".into(), port.to_string().into()]);
+            }
+            arguments
+        }
+        NetLaunch::Join { address, port } => {
+            let target = match port {
+                Some(port) => format!("{}:{}", address, port),
+                None => address.clone(),
+            };
+            vec!["-connect".into(), target.into()]
+        }
+    }
+}
+
+fn get_game_mode_argument(game_mode: GameMode) -> Option<OsString> {
+    match game_mode {
+        GameMode::Coop => None,
+        GameMode::Deathmatch => Some("-deathmatch".into()),
+        GameMode::AltDeath => Some("-altdeath".into()),
+    }
+}
+
+// FNV-1a over the enabled assets' names and sizes, in load order. Cheap enough to compute on
+// every launch and stable across machines, so players can compare it before a session
+pub fn get_asset_fingerprint<'a>(assets: impl Iterator<Item = (&'a str, u64)>) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for (name, size) in assets {
+        for byte in name.bytes().chain([0]).chain(size.to_le_bytes()) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    format!("{:016x}", hash)
+}
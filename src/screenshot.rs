@@ -0,0 +1,49 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time;
+
+use anyhow::Result;
+
+#[derive(Clone)]
+pub struct Screenshot {
+    pub path: PathBuf,
+    pub taken: Option<time::SystemTime>,
+    pub size: u64,
+}
+
+impl Screenshot {
+    pub const DIR_NAME: &'static str = "screenshots";
+    pub const EXTENSIONS: [&'static str; 6] = ["png", "jpg", "jpeg", "tga", "pcx", "bmp"];
+
+    pub fn from_path(path: PathBuf) -> Self {
+        let metadata = fs::metadata(&path).ok();
+        Self {
+            taken: metadata.as_ref().and_then(|m| m.modified().ok()),
+            size: metadata.map(|m| m.len()).unwrap_or(0),
+            path,
+        }
+    }
+
+    pub fn is_screenshot(path: &Path) -> bool {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| Self::EXTENSIONS.iter().any(|known| ext.eq_ignore_ascii_case(known)))
+    }
+
+    // Newest first
+    pub fn list(dir: &Path) -> Result<Vec<Screenshot>> {
+        if !dir.is_dir() {
+            return Ok(vec![]);
+        }
+
+        let mut screenshots: Vec<Screenshot> = fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file() && Self::is_screenshot(path))
+            .map(Screenshot::from_path)
+            .collect();
+
+        screenshots.sort_by_key(|screenshot| std::cmp::Reverse(screenshot.taken));
+        Ok(screenshots)
+    }
+}
@@ -0,0 +1,264 @@
+use std::fs::OpenOptions;
+use std::os::unix::process::ExitStatusExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, ExitStatus};
+use std::time;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::console::OutputCapture;
+use crate::flatpak;
+use crate::heartbeat::{Heartbeat, SessionMarker};
+use crate::history::{SessionHistory, SessionRecord};
+use crate::hooks::{Hooks, get_hook_env};
+use crate::launch::LaunchPlan;
+use crate::instance::Instance;
+use crate::logs::SessionLog;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SessionOutcome {
+    Clean,
+    Error { code: i32 },
+    Crash { signal: i32, core_dumped: bool },
+    Killed,
+    // Recovered from a leftover session marker, the launcher never saw how the game exited
+    Interrupted,
+}
+
+impl SessionOutcome {
+    pub fn from_status(status: ExitStatus) -> Self {
+        match (status.code(), status.signal()) {
+            (Some(0), _) => SessionOutcome::Clean,
+            (Some(code), _) => SessionOutcome::Error { code },
+            (None, Some(signal)) => SessionOutcome::Crash {
+                signal,
+                core_dumped: status.core_dumped(),
+            },
+            // Neither a code nor a signal shouldn't happen on UNIX, treat it like a failure anyway
+            (None, None) => SessionOutcome::Error { code: -1 },
+        }
+    }
+
+    pub fn is_clean(&self) -> bool {
+        matches!(self, SessionOutcome::Clean | SessionOutcome::Killed)
+    }
+
+    pub fn is_crash(&self) -> bool {
+        matches!(self, SessionOutcome::Crash { .. })
+    }
+}
+
+pub struct GameSession {
+    child: Child,
+    start_time: time::SystemTime,
+    // Durations come from this one, `start_time` is only for display and the history
+    started: time::Instant,
+    log_path: PathBuf,
+    // App id when `child` is only the `flatpak run` wrapper around the actual game
+    flatpak_app: Option<String>,
+    hooks: Hooks,
+    engine_name: Option<String>,
+    iwads: Vec<PathBuf>,
+    mods: Vec<PathBuf>,
+    errors: Vec<anyhow::Error>,
+    killed: bool,
+    outcome: Option<SessionOutcome>,
+    heartbeat: Option<Heartbeat>,
+    capture: Option<OutputCapture>,
+}
+
+impl GameSession {
+    pub const CRASH_LOG_LINES: usize = 30;
+
+    pub fn new(child: Child, start_time: time::SystemTime, log_path: PathBuf, plan: &LaunchPlan) -> Self {
+        Self {
+            child,
+            start_time,
+            started: time::Instant::now(),
+            log_path,
+            flatpak_app: plan.sandbox.as_ref().map(|sandbox| sandbox.app_id.clone()),
+            hooks: plan.hooks.clone(),
+            engine_name: plan.engine_name.clone(),
+            iwads: Vec::new(),
+            mods: Vec::new(),
+            errors: Vec::new(),
+            killed: false,
+            outcome: None,
+            heartbeat: None,
+            capture: None,
+        }
+    }
+
+    // Assets loaded for this session, as recorded in the session history
+    pub fn set_assets(&mut self, iwads: Vec<PathBuf>, mods: Vec<PathBuf>) {
+        self.iwads = iwads;
+        self.mods = mods;
+    }
+
+    // Game output to copy into the log and track map changes in, see `OutputCapture`
+    pub fn set_output_capture(&mut self, capture: OutputCapture) {
+        self.capture = Some(capture);
+    }
+
+    // Keeps a marker at `path` up to date until the session is recorded, so the playtime
+    // can still be recovered if the launcher dies first. The game is already running by now,
+    // so failing here only ends up in `get_errors`
+    pub fn start_heartbeat(&mut self, path: PathBuf) {
+        let marker = SessionMarker {
+            pid: self.child.id(),
+            start: self.start_time,
+            last_heartbeat: time::SystemTime::now(),
+            elapsed: self.started.elapsed(),
+            engine: self.engine_name.clone(),
+            iwads: self.iwads.clone(),
+            mods: self.mods.clone(),
+        };
+        match Heartbeat::start(path, marker, self.started) {
+            Ok(heartbeat) => self.heartbeat = Some(heartbeat),
+            Err(e) => self.errors.push(e.context("Failed to start session heartbeat")),
+        }
+    }
+
+    pub fn get_pid(&self) -> u32 {
+        self.child.id()
+    }
+
+    // PID of the game itself, for Flatpak sessions it's looked up inside the sandbox
+    // and is `None` until the sandbox is up
+    pub fn get_game_pid(&self) -> Result<Option<u32>> {
+        match &self.flatpak_app {
+            Some(app_id) => flatpak::get_game_pid(app_id, self.child.id()),
+            None => Ok(Some(self.child.id())),
+        }
+    }
+
+    pub fn is_flatpak(&self) -> bool {
+        self.flatpak_app.is_some()
+    }
+
+    pub fn get_start_time(&self) -> time::SystemTime {
+        self.start_time
+    }
+
+    pub fn get_log_path(&self) -> &Path {
+        &self.log_path
+    }
+
+    pub fn get_outcome(&self) -> Option<SessionOutcome> {
+        self.outcome
+    }
+
+    // Things that went wrong besides the game itself, like failing post-exit hooks or the session
+    // history not being writable. The outcome and playtime are recorded regardless
+    pub fn get_errors(&self) -> &[anyhow::Error] {
+        &self.errors
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.outcome.is_some()
+    }
+
+    // Returns `None` while the game is still running. The first call that sees the process exit
+    // records the session into the instance's metadata, so it's safe to poll this from a UI loop
+    pub fn try_wait(&mut self, instance: &mut Instance) -> Result<Option<SessionOutcome>> {
+        if self.outcome.is_some() {
+            return Ok(self.outcome);
+        }
+
+        match self.child.try_wait().context("Failed to poll game process")? {
+            Some(status) => Ok(Some(self.finish(instance, status))),
+            None => Ok(None),
+        }
+    }
+
+    pub fn wait(&mut self, instance: &mut Instance) -> Result<SessionOutcome> {
+        if let Some(outcome) = self.outcome {
+            return Ok(outcome);
+        }
+
+        let status = self.child.wait().context("Failed to wait for game process")?;
+        Ok(self.finish(instance, status))
+    }
+
+    // Only sends the kill signal, call `wait` afterwards to record the session
+    pub fn kill(&mut self) -> Result<()> {
+        if self.outcome.is_some() {
+            return Ok(());
+        }
+        match &self.flatpak_app {
+            // Killing the wrapper would leave the sandboxed game running
+            Some(app_id) => flatpak::kill_game(app_id, self.child.id())?,
+            None => self.child.kill().context("Failed to kill game process")?,
+        }
+        self.killed = true;
+        Ok(())
+    }
+
+    // The tail of this session's log, but only if the game crashed
+    pub fn get_crash_log(&self) -> Result<Option<Vec<String>>> {
+        match self.outcome {
+            Some(outcome) if outcome.is_crash() => {
+                let log = SessionLog::from_path(self.log_path.clone());
+                Ok(Some(log.tail(Self::CRASH_LOG_LINES)?))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn finish(&mut self, instance: &mut Instance, status: ExitStatus) -> SessionOutcome {
+        let outcome = match (self.killed, SessionOutcome::from_status(status)) {
+            (true, _) => SessionOutcome::Killed,
+            // bwrap reports a game killed by a signal as 128 + signal
+            (false, SessionOutcome::Error { code }) if self.is_flatpak() && (129..160).contains(&code) => {
+                SessionOutcome::Crash {
+                    signal: code - 128,
+                    core_dumped: false,
+                }
+            }
+            (false, outcome) => outcome,
+        };
+        self.outcome = Some(outcome);
+        let ended = time::Instant::now();
+
+        let metadata = &mut instance.metadata;
+        metadata.last_played = Some(self.start_time);
+        metadata.last_session_duration = Some(ended.duration_since(self.started));
+        metadata.last_outcome = Some(outcome);
+
+        if let Some(t) = metadata.last_session_duration {
+            metadata.playtime += t;
+        }
+
+        let record = SessionRecord {
+            start: self.start_time,
+            end: time::SystemTime::now(),
+            duration: metadata.last_session_duration.unwrap_or_default(),
+            engine: self.engine_name.clone(),
+            iwads: self.iwads.clone(),
+            mods: self.mods.clone(),
+            outcome,
+            maps: self.capture.take().map(|capture| capture.finish(ended)).unwrap_or_default(),
+        };
+        if let Err(e) = instance
+            .get_history_path()
+            .and_then(|path| SessionHistory::append_to(&path, record))
+        {
+            self.errors.push(e.context("Failed to record session history"));
+        }
+        if let Some(heartbeat) = self.heartbeat.take()
+            && let Err(e) = heartbeat.finish()
+        {
+            self.errors.push(e);
+        }
+
+        if !self.hooks.post_exit.is_empty() {
+            let log = OpenOptions::new().append(true).open(&self.log_path).ok();
+            let env = get_hook_env(instance, Some(outcome));
+            self.errors.extend(self.hooks.run_post_exit(&env, log.as_ref()));
+        }
+
+        outcome
+    }
+}
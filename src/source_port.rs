@@ -0,0 +1,402 @@
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::cvars::{CvarValue, get_set_cvar_arguments};
+use crate::demo::Demo;
+use crate::engine_config::ConfigRoots;
+use crate::launch_options::get_vanilla_warp_arguments;
+use crate::multiplayer::{NetLaunch, get_server_net_arguments, get_zdoom_net_arguments};
+
+pub const ARCHIVE_EXTENSIONS: [&str; 4] = ["pk3", "pk7", "ipk3", "ipk7"];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Capabilities {
+    // Can load .pk3/.pk7/.ipk3/.ipk7 archives
+    pub archives: bool,
+    // Understands `+set <cvar> <value>` style console commands on the command line
+    pub cvars: bool,
+    pub multiplayer: bool,
+    pub screenshot_dir: bool,
+}
+
+// Describes the command line dialect of an engine. Defaults follow GZDoom,
+// so implementations only override what actually differs
+pub trait SourcePort: Sync {
+    fn get_name(&self) -> &'static str;
+    fn get_capabilities(&self) -> Capabilities;
+
+    // Binary names looked up during engine discovery
+    fn get_executable_names(&self) -> &'static [&'static str];
+
+    fn get_flatpak_ids(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn get_iwad_prefix(&self) -> &'static OsStr {
+        OsStr::new("-iwad")
+    }
+
+    fn get_file_prefix(&self) -> &'static OsStr {
+        OsStr::new("-file")
+    }
+
+    fn get_deh_prefix(&self) -> &'static OsStr {
+        OsStr::new("-deh")
+    }
+
+    // Whether .deh/.bex patches can go through `-file` like everything else
+    fn loads_deh_as_file(&self) -> bool {
+        false
+    }
+
+    // Vanilla and PrBoom style parsers only look at the first `-file`/`-deh` and take the words
+    // after it as the list, so every file has to go under a single prefix there
+    fn groups_file_arguments(&self) -> bool {
+        false
+    }
+
+    fn get_savedir_prefix(&self) -> Option<&'static OsStr> {
+        Some(OsStr::new("-savedir"))
+    }
+
+    fn get_config_prefix(&self) -> Option<&'static OsStr> {
+        Some(OsStr::new("-config"))
+    }
+
+    fn get_config_extension(&self) -> &'static str {
+        "ini"
+    }
+
+    // Where the engine keeps its config when started without `-config`
+    fn get_global_config_path(&self, roots: &ConfigRoots) -> Option<PathBuf> {
+        Some(roots.config_home.join("gzdoom/gzdoom.ini"))
+    }
+
+    // `None` if the engine has no way to start on that map
+    fn get_warp_arguments(&self, map: &str) -> Option<Vec<OsString>> {
+        Some(vec![OsString::from("+map"), OsString::from(map)])
+    }
+
+    fn get_record_arguments(&self, path: &Path) -> Vec<OsString> {
+        vec![OsString::from("-record"), path.as_os_str().to_os_string()]
+    }
+
+    // `None` if screenshots can't be redirected, they land wherever the engine puts them then
+    fn get_screenshot_arguments(&self, dir: &Path) -> Option<Vec<OsString>> {
+        if !self.get_capabilities().screenshot_dir {
+            return None;
+        }
+        Some(vec![OsString::from("-shotdir"), dir.as_os_str().to_os_string()])
+    }
+
+    // `None` if the engine can't set console variables from the command line
+    fn get_cvar_arguments(&self, name: &str, value: &CvarValue) -> Option<Vec<OsString>> {
+        if !self.get_capabilities().cvars {
+            return None;
+        }
+        Some(get_set_cvar_arguments(name, value))
+    }
+
+    // `None` if the engine can't take that role in a netgame
+    fn get_net_arguments(&self, net: &NetLaunch) -> Option<Vec<OsString>> {
+        if !self.get_capabilities().multiplayer {
+            return None;
+        }
+        Some(get_zdoom_net_arguments(net))
+    }
+
+    // Archive formats only ZDoom style engines can load
+    fn can_load(&self, path: &Path) -> bool {
+        let is_archive = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| ARCHIVE_EXTENSIONS.iter().any(|archive| ext.eq_ignore_ascii_case(archive)));
+        !is_archive || self.get_capabilities().archives
+    }
+
+    fn get_mod_prefix(&self, path: &Path) -> &'static OsStr {
+        let is_deh = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("deh") || ext.eq_ignore_ascii_case("bex"));
+
+        if is_deh && !self.loads_deh_as_file() {
+            self.get_deh_prefix()
+        } else {
+            self.get_file_prefix()
+        }
+    }
+}
+
+pub struct GZDoom;
+pub struct DsdaDoom;
+pub struct ChocolateDoom;
+pub struct Eternity;
+pub struct Zandronum;
+pub struct Odamex;
+
+impl SourcePort for GZDoom {
+    fn get_name(&self) -> &'static str {
+        "GZDoom"
+    }
+
+    fn get_executable_names(&self) -> &'static [&'static str] {
+        &["gzdoom"]
+    }
+
+    fn get_flatpak_ids(&self) -> &'static [&'static str] {
+        &["org.zdoom.GZDoom"]
+    }
+
+    fn get_capabilities(&self) -> Capabilities {
+        Capabilities {
+            archives: true,
+            cvars: true,
+            multiplayer: true,
+            screenshot_dir: true,
+        }
+    }
+
+    fn loads_deh_as_file(&self) -> bool {
+        true
+    }
+}
+
+impl SourcePort for DsdaDoom {
+    fn get_name(&self) -> &'static str {
+        "dsda-doom"
+    }
+
+    fn get_executable_names(&self) -> &'static [&'static str] {
+        &["dsda-doom"]
+    }
+
+    fn get_flatpak_ids(&self) -> &'static [&'static str] {
+        &["io.github.kraflab.dsda-doom"]
+    }
+
+    fn get_capabilities(&self) -> Capabilities {
+        Capabilities {
+            archives: false,
+            cvars: false,
+            multiplayer: false,
+            screenshot_dir: false,
+        }
+    }
+
+    fn get_savedir_prefix(&self) -> Option<&'static OsStr> {
+        Some(OsStr::new("-save"))
+    }
+
+    fn groups_file_arguments(&self) -> bool {
+        true
+    }
+
+    fn get_warp_arguments(&self, map: &str) -> Option<Vec<OsString>> {
+        get_vanilla_warp_arguments(map)
+    }
+
+    fn get_config_extension(&self) -> &'static str {
+        "cfg"
+    }
+
+    fn get_global_config_path(&self, roots: &ConfigRoots) -> Option<PathBuf> {
+        Some(roots.data_home.join("dsda-doom/dsda-doom.cfg"))
+    }
+}
+
+impl SourcePort for ChocolateDoom {
+    fn get_name(&self) -> &'static str {
+        "Chocolate Doom"
+    }
+
+    fn get_executable_names(&self) -> &'static [&'static str] {
+        &["chocolate-doom"]
+    }
+
+    fn get_capabilities(&self) -> Capabilities {
+        Capabilities {
+            archives: false,
+            cvars: false,
+            multiplayer: true,
+            screenshot_dir: false,
+        }
+    }
+
+    fn groups_file_arguments(&self) -> bool {
+        true
+    }
+
+    // Always appends `.lmp` to whatever it gets
+    fn get_record_arguments(&self, path: &Path) -> Vec<OsString> {
+        let is_lmp = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| ext.eq_ignore_ascii_case(Demo::EXTENSION));
+        let path = if is_lmp { path.with_extension("") } else { path.to_path_buf() };
+        vec![OsString::from("-record"), path.into_os_string()]
+    }
+
+    fn get_warp_arguments(&self, map: &str) -> Option<Vec<OsString>> {
+        get_vanilla_warp_arguments(map)
+    }
+
+    fn get_net_arguments(&self, net: &NetLaunch) -> Option<Vec<OsString>> {
+        Some(get_server_net_arguments(net))
+    }
+
+    fn get_config_extension(&self) -> &'static str {
+        "cfg"
+    }
+
+    fn get_global_config_path(&self, roots: &ConfigRoots) -> Option<PathBuf> {
+        Some(roots.data_home.join("chocolate-doom/default.cfg"))
+    }
+}
+
+impl SourcePort for Eternity {
+    fn get_name(&self) -> &'static str {
+        "Eternity"
+    }
+
+    fn get_executable_names(&self) -> &'static [&'static str] {
+        &["eternity"]
+    }
+
+    fn get_capabilities(&self) -> Capabilities {
+        Capabilities {
+            archives: true,
+            cvars: false,
+            multiplayer: false,
+            screenshot_dir: false,
+        }
+    }
+
+    fn get_savedir_prefix(&self) -> Option<&'static OsStr> {
+        Some(OsStr::new("-save"))
+    }
+
+    // Eternity keeps its configuration in a user directory rather than a single file
+    fn get_config_prefix(&self) -> Option<&'static OsStr> {
+        None
+    }
+
+    fn get_warp_arguments(&self, map: &str) -> Option<Vec<OsString>> {
+        get_vanilla_warp_arguments(map)
+    }
+
+    fn get_global_config_path(&self, _roots: &ConfigRoots) -> Option<PathBuf> {
+        None
+    }
+}
+
+impl SourcePort for Zandronum {
+    fn get_name(&self) -> &'static str {
+        "Zandronum"
+    }
+
+    fn get_executable_names(&self) -> &'static [&'static str] {
+        &["zandronum"]
+    }
+
+    fn get_capabilities(&self) -> Capabilities {
+        Capabilities {
+            archives: true,
+            cvars: true,
+            multiplayer: true,
+            screenshot_dir: true,
+        }
+    }
+
+    fn loads_deh_as_file(&self) -> bool {
+        true
+    }
+
+    // Client/server like Odamex, joins with `-connect host:port` and hosting needs zandronum-server
+    fn get_net_arguments(&self, net: &NetLaunch) -> Option<Vec<OsString>> {
+        match net {
+            NetLaunch::Host { .. } => None,
+            NetLaunch::Join { .. } => Some(get_server_net_arguments(net)),
+        }
+    }
+
+    fn get_global_config_path(&self, roots: &ConfigRoots) -> Option<PathBuf> {
+        Some(roots.config_home.join("zandronum/zandronum.ini"))
+    }
+}
+
+impl SourcePort for Odamex {
+    fn get_name(&self) -> &'static str {
+        "Odamex"
+    }
+
+    fn get_executable_names(&self) -> &'static [&'static str] {
+        &["odamex"]
+    }
+
+    fn get_flatpak_ids(&self) -> &'static [&'static str] {
+        &["net.odamex.Odamex"]
+    }
+
+    fn get_capabilities(&self) -> Capabilities {
+        Capabilities {
+            archives: true,
+            cvars: true,
+            multiplayer: true,
+            screenshot_dir: false,
+        }
+    }
+
+    // Hosting needs the separate odasrv binary
+    fn get_net_arguments(&self, net: &NetLaunch) -> Option<Vec<OsString>> {
+        match net {
+            NetLaunch::Host { .. } => None,
+            NetLaunch::Join { .. } => Some(get_server_net_arguments(net)),
+        }
+    }
+
+    fn get_config_extension(&self) -> &'static str {
+        "cfg"
+    }
+
+    fn get_global_config_path(&self, roots: &ConfigRoots) -> Option<PathBuf> {
+        Some(roots.home.join(".odamex/odamex.cfg"))
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum SourcePortKind {
+    #[default]
+    #[serde(rename = "gzdoom")]
+    GZDoom,
+    DsdaDoom,
+    ChocolateDoom,
+    Eternity,
+    Zandronum,
+    Odamex,
+}
+
+impl SourcePortKind {
+    pub const ALL: [SourcePortKind; 6] = [
+        SourcePortKind::GZDoom,
+        SourcePortKind::DsdaDoom,
+        SourcePortKind::ChocolateDoom,
+        SourcePortKind::Eternity,
+        SourcePortKind::Zandronum,
+        SourcePortKind::Odamex,
+    ];
+
+    pub fn get_port(&self) -> &'static dyn SourcePort {
+        match self {
+            SourcePortKind::GZDoom => &GZDoom,
+            SourcePortKind::DsdaDoom => &DsdaDoom,
+            SourcePortKind::ChocolateDoom => &ChocolateDoom,
+            SourcePortKind::Eternity => &Eternity,
+            SourcePortKind::Zandronum => &Zandronum,
+            SourcePortKind::Odamex => &Odamex,
+        }
+    }
+}
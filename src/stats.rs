@@ -0,0 +1,168 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::time;
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::history::SessionHistory;
+
+const SECS_PER_DAY: u64 = 86_400;
+
+// Playtime in seconds throughout, so exports stay readable in a spreadsheet.
+// Sessions count towards the day (UTC) they started on
+#[derive(Serialize, Clone, Default)]
+pub struct LibraryStats {
+    pub total_playtime: u64,
+    pub session_count: usize,
+    // `YYYY-MM-DD`
+    pub per_day: BTreeMap<String, u64>,
+    // Keyed by the week's Monday, `YYYY-MM-DD`
+    pub per_week: BTreeMap<String, u64>,
+    // `YYYY-MM`
+    pub per_month: BTreeMap<String, u64>,
+    pub per_instance: BTreeMap<String, u64>,
+    // IWAD file name, sessions with several IWADs count towards each
+    pub per_iwad: BTreeMap<String, u64>,
+    pub longest_streak: u32,
+    // Days in a row up to today, or up to yesterday if nothing was played today yet
+    pub current_streak: u32,
+}
+
+impl LibraryStats {
+    pub fn from_histories<'a>(histories: impl IntoIterator<Item = (&'a str, &'a SessionHistory)>) -> Self {
+        let mut stats = LibraryStats::default();
+        let mut played_days = BTreeSet::new();
+
+        for (name, history) in histories {
+            for session in &history.sessions {
+                let secs = session.duration.as_secs();
+                let day = get_day_number(session.start);
+                played_days.insert(day);
+
+                stats.total_playtime += secs;
+                stats.session_count += 1;
+                *stats.per_day.entry(format_day(day)).or_default() += secs;
+                *stats.per_week.entry(format_day(day - get_weekday(day))).or_default() += secs;
+                *stats.per_month.entry(format_month(day)).or_default() += secs;
+                *stats.per_instance.entry(name.to_string()).or_default() += secs;
+
+                for iwad in &session.iwads {
+                    let iwad_name = iwad
+                        .file_name()
+                        .unwrap_or(iwad.as_os_str())
+                        .to_string_lossy()
+                        .into_owned();
+                    *stats.per_iwad.entry(iwad_name).or_default() += secs;
+                }
+            }
+        }
+
+        stats.longest_streak = get_longest_streak(&played_days);
+        stats.current_streak = get_current_streak(&played_days, get_day_number(time::SystemTime::now()));
+        stats
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).context("Failed to serialize to JSON")
+    }
+
+    // Flat `category,key,value` rows, values are seconds except for session counts and streaks
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("category,key,value\n");
+        let mut push_row = |category: &str, key: &str, value: u64| {
+            csv.push_str(&format!("{},{},{}\n", category, escape_csv(key), value));
+        };
+
+        push_row("total", "playtime", self.total_playtime);
+        push_row("total", "sessions", self.session_count as u64);
+        push_row("streak", "longest", self.longest_streak as u64);
+        push_row("streak", "current", self.current_streak as u64);
+
+        let tables = [
+            ("day", &self.per_day),
+            ("week", &self.per_week),
+            ("month", &self.per_month),
+            ("instance", &self.per_instance),
+            ("iwad", &self.per_iwad),
+        ];
+        for (category, table) in tables {
+            for (key, secs) in table {
+                push_row(category, key, *secs);
+            }
+        }
+
+        csv
+    }
+}
+
+fn escape_csv(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn get_longest_streak(days: &BTreeSet<i64>) -> u32 {
+    let mut longest = 0;
+    let mut current = 0;
+    let mut previous: Option<i64> = None;
+
+    for &day in days {
+        current = match previous {
+            Some(p) if day == p + 1 => current + 1,
+            _ => 1,
+        };
+        longest = longest.max(current);
+        previous = Some(day);
+    }
+
+    longest
+}
+
+fn get_current_streak(days: &BTreeSet<i64>, today: i64) -> u32 {
+    let mut day = if days.contains(&today) { today } else { today - 1 };
+    let mut streak = 0;
+    while days.contains(&day) {
+        streak += 1;
+        day -= 1;
+    }
+    streak
+}
+
+// Days since 1970-01-01 (UTC), negative before that
+fn get_day_number(t: time::SystemTime) -> i64 {
+    match t.duration_since(time::UNIX_EPOCH) {
+        Ok(d) => (d.as_secs() / SECS_PER_DAY) as i64,
+        Err(e) => -((e.duration().as_secs() / SECS_PER_DAY) as i64) - 1,
+    }
+}
+
+// 0 = Monday, 1970-01-01 was a Thursday
+fn get_weekday(day: i64) -> i64 {
+    (day + 3).rem_euclid(7)
+}
+
+// Howard Hinnant's days → civil date algorithm
+fn get_civil_date(day: i64) -> (i64, u32, u32) {
+    let z = day + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+    (y, m, d)
+}
+
+fn format_day(day: i64) -> String {
+    let (y, m, d) = get_civil_date(day);
+    format!("{:04}-{:02}-{:02}", y, m, d)
+}
+
+fn format_month(day: i64) -> String {
+    let (y, m, _) = get_civil_date(day);
+    format!("{:04}-{:02}", y, m)
+}
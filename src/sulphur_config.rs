@@ -1,23 +1,58 @@
 use std::ffi::OsString;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
+use anyhow::{Result, bail};
 use serde::{Deserialize, Serialize};
 use xdg::BaseDirectories;
 
+use crate::brimpkg::ConflictStrategy;
+use crate::discovery::discover_engines;
+use crate::engine::Engine;
+use crate::hooks::Hooks;
 use crate::instance::Instance;
+use crate::launch::LaunchPlan;
+use crate::launch_options::LaunchOptions;
+use crate::stats::LibraryStats;
+use crate::source_port::{SourcePort, SourcePortKind};
 use crate::traits::{Saveable, SaveableDefaultPath};
 
+// Instance indices, see `SulphurConfig::recover_interrupted_sessions`
+#[derive(Default)]
+pub struct SessionRecovery {
+    pub recovered: Vec<usize>,
+    pub failed: Vec<(usize, anyhow::Error)>,
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct SulphurConfig {
     pub gzdoom_command: OsString,
+    #[serde(default)]
+    pub source_port: SourcePortKind,
+    #[serde(default)]
+    pub engines: Vec<Engine>,
+    #[serde(default)]
+    pub hooks: Hooks,
+    // Passed to every instance, before engine and instance parameters
+    #[serde(default)]
+    pub additional_params: Vec<OsString>,
+    // How brimpkg imports handle mods and IWADs that clash with existing ones
+    #[serde(default)]
+    pub import_conflicts: ConflictStrategy,
     pub instances: Vec<Instance>,
 }
 
 impl SulphurConfig {
+    pub const DEFAULT_ENGINE_NAME: &'static str = "default";
+
     pub fn new() -> Self {
         Self {
             gzdoom_command: OsString::from("gzdoom"),
+            source_port: SourcePortKind::default(),
+            engines: Vec::new(),
+            hooks: Hooks::default(),
+            additional_params: Vec::new(),
+            import_conflicts: ConflictStrategy::default(),
             instances: Vec::new(),
         }
     }
@@ -33,16 +68,13 @@ impl SulphurConfig {
         Ok(())
     }
 
-    fn find_assets_in_subdir(subdir: &str) -> Result<Vec<PathBuf>, std::io::Error> {
+    pub fn find_assets_in_subdir(subdir: &str) -> Result<Vec<PathBuf>, std::io::Error> {
         let dir_path = match SulphurConfig::get_dir().find_data_file(subdir) {
             Some(path) => path,
             None => return Ok(vec![]),
         };
 
-        let read_dir = match fs::read_dir(dir_path) {
-            Ok(rd) => rd,
-            Err(e) => return Err(e),
-        };
+        let read_dir = fs::read_dir(dir_path)?;
 
         Ok(read_dir
             .filter_map(|entry| {
@@ -62,6 +94,96 @@ impl SulphurConfig {
         self.gzdoom_command = new;
     }
 
+    pub fn get_source_port(&self) -> &'static dyn SourcePort {
+        self.source_port.get_port()
+    }
+
+    pub fn set_source_port(&mut self, new: SourcePortKind) {
+        self.source_port = new;
+    }
+
+    // `gzdoom_command` and `source_port` act as the engine for every instance that doesn't pin one
+    pub fn get_default_engine(&self) -> Engine {
+        Engine::new(
+            Self::DEFAULT_ENGINE_NAME.to_string(),
+            self.gzdoom_command.clone(),
+            self.source_port,
+        )
+    }
+
+    pub fn get_engines(&self) -> Vec<&Engine> {
+        self.engines.iter().collect()
+    }
+
+    pub fn get_engine(&self, name: &str) -> Option<&Engine> {
+        self.engines.iter().find(|engine| engine.name == name)
+    }
+
+    pub fn get_engine_mut(&mut self, name: &str) -> Option<&mut Engine> {
+        self.engines.iter_mut().find(|engine| engine.name == name)
+    }
+
+    pub fn add_engine(&mut self, new_engine: Engine) -> Result<usize> {
+        if new_engine.name == Self::DEFAULT_ENGINE_NAME || self.get_engine(&new_engine.name).is_some() {
+            bail!("An engine named {} already exists", new_engine.name);
+        }
+        self.engines.push(new_engine);
+        Ok(self.engines.len() - 1)
+    }
+
+    // Instances pinned to the removed engine get unpinned and go back to the default one, their
+    // indices are returned along with the engine so that can be shown
+    pub fn remove_engine(&mut self, name: &str) -> Option<(Engine, Vec<usize>)> {
+        let position = self.engines.iter().position(|engine| engine.name == name)?;
+        let mut unpinned = Vec::new();
+        for (index, instance) in self.instances.iter_mut().enumerate() {
+            if instance.engine.as_deref() == Some(name) {
+                instance.set_engine(None);
+                unpinned.push(index);
+            }
+        }
+        Some((self.engines.remove(position), unpinned))
+    }
+
+    // Adds every discovered engine whose command isn't registered yet, returns the new names
+    pub fn register_discovered_engines(&mut self) -> Vec<String> {
+        let mut added = Vec::new();
+        for engine in discover_engines() {
+            let known = engine.command == self.gzdoom_command
+                || self.engines.iter().any(|e| e.command == engine.command);
+            if known || self.get_engine(&engine.name).is_some() {
+                continue;
+            }
+            added.push(engine.name.clone());
+            self.engines.push(engine);
+        }
+        added
+    }
+
+    // A pinned engine that doesn't exist is an error rather than a silent switch to the default,
+    // an instance pinned to one port shouldn't quietly start on another
+    pub fn get_engine_for(&self, instance: &Instance) -> Result<Engine> {
+        match instance.engine.as_deref() {
+            Some(name) => match self.get_engine(name) {
+                Some(engine) => Ok(engine.clone()),
+                None => bail!("{} is pinned to engine {}, which doesn't exist", instance.metadata.name, name),
+            },
+            None => Ok(self.get_default_engine()),
+        }
+    }
+
+    pub fn get_launch_plan(&self, instance: &Instance, overrides: &LaunchOptions) -> Result<LaunchPlan> {
+        let engine = self.get_engine_for(instance)?;
+        let mut plan = instance.get_layered_launch_plan(&engine, &self.additional_params, overrides)?;
+        plan.hooks = instance.hooks.wrapped_by(&self.hooks);
+        Ok(plan)
+    }
+
+    // Exactly what `get_launch_plan` would run, for showing or copy-pasting
+    pub fn get_full_command(&self, instance: &Instance, overrides: &LaunchOptions) -> Result<OsString> {
+        Ok(self.get_launch_plan(instance, overrides)?.to_shell_string())
+    }
+
     pub fn get_instances(&self) -> Vec<&Instance> {
         self.instances.iter().collect()
     }
@@ -114,6 +236,40 @@ impl SulphurConfig {
             .collect()
     }
 
+    // Reads every instance's session history, so better not call this every frame. Instances
+    // whose history can't be read are left out of the stats and returned by index instead
+    pub fn get_stats(&self) -> (LibraryStats, Vec<(usize, anyhow::Error)>) {
+        let mut histories = Vec::new();
+        let mut failed = Vec::new();
+        for (index, instance) in self.instances.iter().enumerate() {
+            match instance.get_history() {
+                Ok(history) => histories.push((instance.metadata.name.as_str(), history)),
+                Err(e) => failed.push((index, e)),
+            }
+        }
+        let stats = LibraryStats::from_histories(histories.iter().map(|(name, history)| (*name, history)));
+        (stats, failed)
+    }
+
+    // Meant to run once on startup, save the config afterwards if anything was recovered.
+    // One instance failing doesn't stop the others, its marker stays around for the next try
+    pub fn recover_interrupted_sessions(&mut self) -> SessionRecovery {
+        let mut recovery = SessionRecovery::default();
+        for (index, instance) in self.instances.iter_mut().enumerate() {
+            match instance.recover_interrupted_session() {
+                Ok(Some(_)) => recovery.recovered.push(index),
+                Ok(None) => {}
+                Err(e) => recovery.failed.push((index, e)),
+            }
+        }
+        recovery
+    }
+
+    pub fn import_brimpkg(&mut self, path: &Path) -> Result<usize> {
+        let instance = Instance::load_brimpkg(path, self.import_conflicts)?;
+        Ok(self.add_instance(instance))
+    }
+
     pub fn add_instance(&mut self, new_inst: Instance) -> usize {
         self.instances.push(new_inst);
         self.instances.len() - 1
@@ -124,6 +280,12 @@ impl SulphurConfig {
     }
 }
 
+impl Default for SulphurConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Saveable for SulphurConfig {}
 impl SaveableDefaultPath for SulphurConfig {
     fn get_dir() -> BaseDirectories {
@@ -1,6 +1,6 @@
 use std::ffi::OsStr;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
@@ -8,9 +8,11 @@ use xdg::BaseDirectories;
 
 use crate::SulphurConfig;
 use crate::asset::Asset;
+use crate::source_port::SourcePort;
+use crate::utils::get_absolute_data_path;
 
 pub trait Argument {
-    fn get_prefix() -> &'static OsStr;
+    fn get_prefix(port: &dyn SourcePort, path: &Path) -> &'static OsStr;
 }
 
 pub trait Movable {
@@ -81,18 +83,7 @@ pub trait Movable {
     where
         Self: AsRef<Asset>,
     {
-        let asset = self.as_ref();
-        let path = &asset.path;
-
-        if path.is_absolute() {
-            return Some(path.clone());
-        }
-
-        if let Ok(base_dir) = SulphurConfig::get_dir().place_data_file("") {
-            return Some(base_dir.join(path));
-        }
-
-        None
+        get_absolute_data_path(&self.as_ref().path)
     }
 }
 
@@ -112,6 +103,15 @@ pub trait Saveable: Serialize + for<'de> Deserialize<'de> {
             .with_context(|| format!("Failed to write to {}", path.display()))?;
         Ok(())
     }
+
+    // Goes through a temporary file next to `path`, a power loss mid-write leaves either the old
+    // or the new content but never a truncated file
+    fn save_atomically(&self, path: &Path) -> Result<()> {
+        let tmp_path = path.with_extension("toml.tmp");
+        self.save_as(tmp_path.clone())?;
+        fs::rename(&tmp_path, path).with_context(|| format!("Failed to write to {}", path.display()))
+    }
+
     fn load_from(path: PathBuf) -> Result<Self> {
         let content = fs::read_to_string(&path)
             .with_context(|| format!("Failed to read from {}", path.display()))?;
@@ -1,24 +1,44 @@
 use std::ffi::OsString;
+use std::path::{Path, PathBuf};
 
 use crate::traits::{Argument, Movable};
 use crate::asset::Asset;
+use crate::source_port::SourcePort;
+use crate::{SaveableDefaultPath, SulphurConfig};
 
-pub fn get_argument<T: Argument + Movable + AsRef<Asset>>(element: &T) -> OsString {
-    let mut argument = OsString::new();
-    if let Some(p) = element.get_absolute_path() {
-        argument.push(T::get_prefix());
-        argument.push(" \"");
-        argument.push(p.as_os_str());
-        argument.push("\"");
+pub fn get_argument<T: Argument + Movable + AsRef<Asset>>(element: &T, port: &dyn SourcePort) -> Vec<OsString> {
+    match element.get_absolute_path() {
+        Some(p) => vec![T::get_prefix(port, &p).to_os_string(), p.into_os_string()],
+        None => Vec::new(),
     }
-    argument
 }
 
-pub fn get_arguments<T>(assets: &[T]) -> impl Iterator<Item = OsString>
+pub fn get_arguments<T>(assets: &[T], port: &dyn SourcePort) -> Vec<OsString>
 where
     T: Argument + Movable + AsRef<Asset>,
 {
-    get_enabled(assets).map(|asset| get_argument(asset))
+    if !port.groups_file_arguments() {
+        return get_enabled(assets).flat_map(|asset| get_argument(asset, port)).collect();
+    }
+
+    // `-file a b -deh c`, each prefix once in the order it first shows up, keeping the load
+    // order of the files under it
+    let mut groups: Vec<(OsString, Vec<OsString>)> = Vec::new();
+    for asset in get_enabled(assets) {
+        let Some(path) = asset.get_absolute_path() else {
+            continue;
+        };
+        let prefix = T::get_prefix(port, &path).to_os_string();
+        match groups.iter_mut().find(|(group_prefix, _)| *group_prefix == prefix) {
+            Some((_, paths)) => paths.push(path.into_os_string()),
+            None => groups.push((prefix, vec![path.into_os_string()])),
+        }
+    }
+
+    groups
+        .into_iter()
+        .flat_map(|(prefix, paths)| std::iter::once(prefix).chain(paths))
+        .collect()
 }
 
 pub fn get_enabled<T>(assets: &[T]) -> impl Iterator<Item = &T>
@@ -27,3 +47,16 @@ where
 {
     assets.iter().filter(|asset| asset.as_ref().enabled)
 }
+
+// Relative paths are relative to the sulphur data dir, which keeps instances portable
+pub fn get_absolute_data_path(path: &Path) -> Option<PathBuf> {
+    if path.is_absolute() {
+        return Some(path.to_path_buf());
+    }
+
+    if let Ok(base_dir) = SulphurConfig::get_dir().place_data_file("") {
+        return Some(base_dir.join(path));
+    }
+
+    None
+}